@@ -1,95 +1,375 @@
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::char,
+    character::complete::one_of,
     character::complete::{digit1 as digit, space0 as space},
     combinator::map_res,
     multi::fold_many0,
-    sequence::{delimited, pair},
+    sequence::{delimited, pair, preceded},
     IResult,
 };
 
 // Parser definition
 
+use std::fmt;
+use std::ops::Range;
 use std::str::FromStr;
+use crate::common::{Card, CardType, JokerRule, Rejection};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+// The answer as the player wrote it, parens included, so it can be
+// checked against the hand before doing any math
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Expr {
+    Number(i64),
+    Joker(i64),     // a wild joker with the value the player gave it
+
+    BinOp(Op, Box<Expr>, Box<Expr>),
+    Parens(Box<Expr>),
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct CardsError {
+    pub unused: Vec<Card>,      // cards of the hand that are not in the answer
+    pub extra:  Vec<i64>,       // numbers that no card of the hand has
+    pub reused: Vec<Card>,      // cards written more times than dealt
+    pub jokers: Vec<i64>,       // joker values with no wild joker to take them
+}
+
+impl Op {
+    fn from_char(c: char) -> Self {
+        match c {
+            '+' => Op::Add,
+            '-' => Op::Sub,
+            '*' => Op::Mul,
+            _ => Op::Div,
+        }
+    }
+
+    pub fn symbol(&self) -> char {
+        match self {
+            Op::Add => '+',
+            Op::Sub => '-',
+            Op::Mul => '*',
+            Op::Div => '/',
+        }
+    }
+}
+
+impl Expr {
+    pub fn numbers(&self) -> Vec<i64> {
+        match self {
+            Expr::Number(n) => vec![*n],
+            Expr::Joker(_) => vec![],
+            Expr::BinOp(_, left, right) => {
+                let mut numbers = left.numbers();
+                numbers.append(&mut right.numbers());
+                numbers
+            }
+            Expr::Parens(inner) => inner.numbers(),
+        }
+    }
+
+    // The values given to the wild jokers
+    pub fn jokers(&self) -> Vec<i64> {
+        match self {
+            Expr::Number(_) => vec![],
+            Expr::Joker(value) => vec![*value],
+            Expr::BinOp(_, left, right) => {
+                let mut jokers = left.jokers();
+                jokers.append(&mut right.jokers());
+                jokers
+            }
+            Expr::Parens(inner) => inner.jokers(),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Joker(value) => write!(f, "J={}", value),
+            Expr::BinOp(op, left, right) => write!(f, "{}{}{}", left, op.symbol(), right),
+            Expr::Parens(inner) => write!(f, "({})", inner),
+        }
+    }
+}
+
+impl CardsError {
+    // One reason for each kind of mistake, in the order a player would fix them
+    pub fn rejections(&self) -> Vec<Rejection> {
+        let mut reasons = Vec::new();
+        if !self.unused.is_empty() {
+            reasons.push(Rejection::UnusedCards(self.unused.clone()));
+        }
+        if !self.extra.is_empty() {
+            reasons.push(Rejection::NotInHand(self.extra.clone()));
+        }
+        if !self.reused.is_empty() {
+            reasons.push(Rejection::ReusedCards(self.reused.clone()));
+        }
+        if !self.jokers.is_empty() {
+            reasons.push(Rejection::NoWildJoker(self.jokers.clone()));
+        }
+        reasons
+    }
+}
+
+// Every number of the answer has to take its own card of the hand,
+// every `J=` a wild joker, and every card of the hand has to be taken
+pub fn check_cards(expr: &Expr, hand: &[Card], jokers: JokerRule) -> Result<(), CardsError> {
+    let mut remaining: Vec<Card> = hand.to_vec();
+    let mut error = CardsError::default();
+
+    for n in expr.numbers() {
+        match remaining.iter().position(|card| jokers.face_value(card) == Some(n)) {
+            Some(i) => { remaining.remove(i); }
+            None => match hand.iter().find(|card| jokers.face_value(card) == Some(n)) {
+                Some(card) => error.reused.push(*card),
+                None => error.extra.push(n),
+            },
+        }
+    }
+    for value in expr.jokers() {
+        let wild = remaining.iter().position(|card| card._type == CardType::Joker);
+        match wild {
+            Some(i) if jokers.wild_values().contains(&value) => { remaining.remove(i); }
+            _ => error.jokers.push(value),
+        }
+    }
+    error.unused = remaining;
+
+    if error == CardsError::default() {
+        Ok(())
+    } else {
+        Err(error)
+    }
+}
+
+// What the numbers typed so far take from the hand, for an answer still
+// being written and maybe not parsing yet
+#[derive(PartialEq, Eq, Debug, Default)]
+pub struct Usage {
+    pub used: Vec<bool>,                // per card of the hand
+    pub unmatched: Vec<Range<usize>>,   // chars of the numbers no card left can take
+}
+
+// Marks the first card not used yet that fits
+fn take(hand: &[Card], used: &mut [bool], fits: impl Fn(&Card) -> bool) -> bool {
+    match hand.iter().enumerate().position(|(i, card)| !used[i] && fits(card)) {
+        Some(i) => { used[i] = true; true }
+        None => false,
+    }
+}
+
+pub fn card_usage(typed: &str, hand: &[Card], jokers: JokerRule) -> Usage {
+    let mut used = vec![false; hand.len()];
+    let mut unmatched = Vec::new();
+    let typed: Vec<char> = typed.trim_end().chars().collect();
+    let mut i = 0;
+    while i < typed.len() {
+        let start = i;
+        if typed[i] == 'J' || typed[i] == 'j' {
+            // the value after `=` belongs to the joker
+            i += 1;
+            while i < typed.len() && (typed[i] == '=' || typed[i] == ' ' || typed[i].is_ascii_digit()) {
+                i += 1;
+            }
+            if !take(hand, &mut used, |card| card._type == CardType::Joker && matches!(jokers, JokerRule::Wild(_))) {
+                unmatched.push(start..i);
+            }
+        } else if typed[i].is_ascii_digit() {
+            while i < typed.len() && typed[i].is_ascii_digit() {
+                i += 1;
+            }
+            let number: String = typed[start..i].iter().collect();
+            let n = number.parse::<i64>().unwrap_or(-1);
+            if !take(hand, &mut used, |card| jokers.face_value(card) == Some(n)) {
+                // the last number may still be half written, `1` on the way to `12`
+                let typing = i == typed.len() && hand.iter().enumerate().any(|(k, card)| !used[k]
+                    && jokers.face_value(card).is_some_and(|value| value.to_string().starts_with(&number)));
+                if !typing {
+                    unmatched.push(start..i);
+                }
+            }
+        } else {
+            i += 1;
+        }
+    }
+    Usage { used, unmatched }
+}
+
+fn bin_op(acc: Expr, (op, val): (char, Expr)) -> Expr {
+    Expr::BinOp(Op::from_char(op), Box::new(acc), Box::new(val))
+}
 
 // We parse any expr surrounded by parens, ignoring all whitespaces around those
-fn parens(i: &str) -> IResult<&str, i64> {
-    delimited(space, delimited(tag("("), expr, tag(")")), space)(i)
+fn parens(i: &str) -> IResult<&str, Expr> {
+    let (i, inner) = delimited(space, delimited(tag("("), expr, tag(")")), space)(i)?;
+    Ok((i, Expr::Parens(Box::new(inner))))
+}
+
+fn number(i: &str) -> Result<Expr, std::num::ParseIntError> {
+    i64::from_str(i).map(Expr::Number)
+}
+
+fn joker_value(i: &str) -> Result<Expr, std::num::ParseIntError> {
+    i64::from_str(i).map(Expr::Joker)
 }
 
-// We transform an integer string into a i64, ignoring surrounding whitespaces
+// A wild joker is written with the value it takes, `J=7`
+fn joker(i: &str) -> IResult<&str, Expr> {
+    let binding = preceded(pair(one_of("Jj"), delimited(space, tag("="), space)), digit);
+    map_res(delimited(space, binding, space), joker_value)(i)
+}
+
+// We transform an integer string into a number leaf, ignoring surrounding whitespaces
 // We look for a digit suite, and try to convert it.
 // If either str::from_utf8 or FromStr::from_str fail,
 // we fallback to the parens parser defined above
-fn factor(i: &str) -> IResult<&str, i64> {
+fn factor(i: &str) -> IResult<&str, Expr> {
     alt((
-        map_res(delimited(space, digit, space), FromStr::from_str),
+        map_res(delimited(space, digit, space), number),
+        joker,
         parens,
     ))(i)
 }
 
 // We read an initial factor and for each time we find
-// a * or / operator followed by another factor, we do
-// the math by folding everything
-fn term(i: &str) -> IResult<&str, i64> {
+// a * or / operator followed by another factor, we
+// build the tree to the left by folding everything
+fn term(i: &str) -> IResult<&str, Expr> {
     let (i, init) = factor(i)?;
 
     fold_many0(
-        pair(alt((char('*'), char('/'))), factor),
-        move || init,
-        |acc, (op, val): (char, i64)| {
-            if op == '*' {
-                acc * val
-            } else {
-                acc / val
-            }
-        },
+        pair(one_of("*/"), factor),
+        move || init.clone(),
+        bin_op,
     )(i)
 }
 
-pub fn expr(i: &str) -> IResult<&str, i64> {
+pub fn expr(i: &str) -> IResult<&str, Expr> {
 
     let (i, init) = term(i)?;
 
     fold_many0(
-        pair(alt((char('+'), char('-'))), term),
-        move || init,
-        |acc, (op, val): (char, i64)| {
-            if op == '+' {
-                acc + val
-            } else {
-                acc - val
-            }
-        },
+        pair(one_of("+-"), term),
+        move || init.clone(),
+        bin_op,
     )(i)
 }
 
+// Parses a whole answer, on error gives the 1-based column where it stops
+pub fn parse(answer: &str) -> Result<Expr, usize> {
+    let column = |rest: &str| answer[..answer.len() - rest.len()].chars().count() + 1;
+    match expr(answer) {
+        Ok(("", tree)) => Ok(tree),
+        Ok((rest, _)) => Err(column(rest)),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(column(e.input)),
+        Err(nom::Err::Incomplete(_)) => Err(answer.chars().count() + 1),
+    }
+}
+
+#[cfg(test)]
+fn num(n: i64) -> Box<Expr> {
+    Box::new(Expr::Number(n))
+}
+
 #[test]
 fn factor_test() {
-    assert_eq!(factor("3"), Ok(("", 3)));
-    assert_eq!(factor(" 12"), Ok(("", 12)));
-    assert_eq!(factor("537  "), Ok(("", 537)));
-    assert_eq!(factor("  24   "), Ok(("", 24)));
+    assert_eq!(factor("3"), Ok(("", Expr::Number(3))));
+    assert_eq!(factor(" 12"), Ok(("", Expr::Number(12))));
+    assert_eq!(factor("537  "), Ok(("", Expr::Number(537))));
+    assert_eq!(factor("  24   "), Ok(("", Expr::Number(24))));
+    assert_eq!(factor(" J=7 "), Ok(("", Expr::Joker(7))));
+    assert_eq!(factor("j = 12"), Ok(("", Expr::Joker(12))));
 }
 
 #[test]
 fn term_test() {
-    assert_eq!(term(" 12 *2 /  3"), Ok(("", 8)));
-    assert_eq!(term(" 2* 3  *2 *2 /  3"), Ok(("", 8)));
-    assert_eq!(term(" 48 /  3/2"), Ok(("", 8)));
+    assert_eq!(term(" 12 *2 /  3").map(|(i, e)| (i, e.to_string())), Ok(("", "12*2/3".to_string())));
+    assert_eq!(
+        term(" 48 /  3/2"),
+        Ok(("", Expr::BinOp(Op::Div, Box::new(Expr::BinOp(Op::Div, num(48), num(3))), num(2))))
+    );
 }
 
 #[test]
 fn expr_test() {
-    assert_eq!(expr(" 1 +  2 "), Ok(("", 3)));
-    assert_eq!(expr(" 12 + 6 - 4+  3"), Ok(("", 17)));
-    assert_eq!(expr(" 1 + 2*3 + 4"), Ok(("", 11)));
+    assert_eq!(expr(" 1 +  2 "), Ok(("", Expr::BinOp(Op::Add, num(1), num(2)))));
+    assert_eq!(expr(" 12 + 6 - 4+  3").map(|(_, e)| e.numbers()), Ok(vec![12, 6, 4, 3]));
+    assert_eq!(
+        expr(" 1 + 2*3 + 4"),
+        Ok(("", Expr::BinOp(Op::Add,
+                            Box::new(Expr::BinOp(Op::Add, num(1), Box::new(Expr::BinOp(Op::Mul, num(2), num(3))))),
+                            num(4))))
+    );
 }
 
 #[test]
 fn parens_test() {
-    assert_eq!(expr(" (  2 )"), Ok(("", 2)));
-    assert_eq!(expr(" 2* (  3 + 4 ) "), Ok(("", 14)));
-    assert_eq!(expr("  2*2 / ( 5 - 1) + 3"), Ok(("", 4)));
-}
\ No newline at end of file
+    assert_eq!(expr(" (  2 )"), Ok(("", Expr::Parens(num(2)))));
+    assert_eq!(expr(" 2* (  3 + 4 ) ").map(|(_, e)| e.to_string()), Ok("2*(3+4)".to_string()));
+    assert_eq!(parse("  2*2 / ( 5 - 1) + 3").map(|e| e.to_string()), Ok("2*2/(5-1)+3".to_string()));
+    assert_eq!(parse("2*(3+4"), Err(2));
+}
+
+#[test]
+fn check_cards_test() {
+    use crate::common::CardType;
+    let hand = [
+        Card::new(CardType::Cup, 1),
+        Card::new(CardType::Gold, 2),
+        Card::new(CardType::Sword, 3),
+        Card::new(CardType::Club, 4),
+    ];
+    assert_eq!(check_cards(&parse("(1+2+3)*4").unwrap(), &hand, JokerRule::Excluded), Ok(()));
+    assert_eq!(
+        check_cards(&parse("12*(3-4+3)").unwrap(), &hand, JokerRule::Excluded),
+        Err(CardsError { unused: vec![hand[0], hand[1]], extra: vec![12], reused: vec![hand[2]], jokers: vec![] })
+    );
+}
+
+#[test]
+fn check_jokers_test() {
+    let joker = Card::new(CardType::Joker, 0);
+    let hand = [joker, Card::new(CardType::Gold, 2), Card::new(CardType::Sword, 3)];
+    let answer = parse("J=4*2*3").unwrap();
+    assert_eq!(check_cards(&answer, &hand, JokerRule::Wild(12)), Ok(()));
+    assert_eq!(
+        check_cards(&answer, &hand, JokerRule::Fixed(4)),
+        Err(CardsError { unused: vec![joker], jokers: vec![4], ..CardsError::default() })
+    );
+    assert_eq!(check_cards(&parse("4*2*3").unwrap(), &hand, JokerRule::Fixed(4)), Ok(()));
+    assert_eq!(
+        check_cards(&parse("J=13*2*3").unwrap(), &hand, JokerRule::Wild(12)),
+        Err(CardsError { unused: vec![joker], jokers: vec![13], ..CardsError::default() })
+    );
+    // the king of a French deck
+    assert_eq!(check_cards(&parse("J=13*2-3").unwrap(), &hand, JokerRule::Wild(13)), Ok(()));
+}
+
+#[test]
+fn card_usage_test() {
+    let hand = [
+        Card::new(CardType::Joker, 1),
+        Card::new(CardType::Gold, 2),
+        Card::new(CardType::Sword, 2),
+        Card::new(CardType::Club, 12),
+    ];
+    let usage = |typed| card_usage(typed, &hand, JokerRule::Wild(12));
+    assert_eq!(usage("(12+2"), Usage { used: vec![false, true, false, true], unmatched: vec![] });
+    assert_eq!(usage("J=2*2+2"), Usage { used: vec![true, true, true, false], unmatched: vec![] });
+    assert_eq!(usage("1"), Usage { used: vec![false; 4], unmatched: vec![] });
+    assert_eq!(usage("1+").unmatched, vec![Range { start: 0, end: 1 }]);
+    assert_eq!(usage("2*2*2 ").used, vec![false, true, true, false]);
+    assert_eq!(usage("2*2*2 ").unmatched, vec![Range { start: 4, end: 5 }]);
+}
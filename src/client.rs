@@ -1,107 +1,219 @@
 use std::io;
-use std::io::Write;
 
-use super::common::{AnswerData, FromServerMessage, FromClientMessage, BYTECOUNT};
+use super::common::{FromServerMessage, FromClientMessage, Handshake, MAX_ANSWER, Rejection};
 use crate::terminal::VisualDeck;
+use crate::theme::Theme;
 
-use message_io::network::{NetEvent, Transport, RemoteAddr};
-use message_io::node::{self, NodeEvent};
+use message_io::network::{Endpoint, NetEvent, Transport, RemoteAddr};
+use message_io::node::{self, NodeEvent, NodeHandler};
 
-use std::time::{Duration};
-use termion::screen::AlternateScreen;
-use crate::command::get_command;
-use crate::common::{Card, CARDCOUNT, CardType, HandCardData};
+use std::thread;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::time::{Duration, Instant};
+use termion::event::Key;
+use termion::input::TermRead;
+use crate::command::{Deadline, Prompt};
+use crate::common::{CARDCOUNT, TableConfig, TableInfo, TurnEndType};
+
+// Typed instead of an answer to claim that the hand has no solution
+const NO_SOLUTION_COMMAND: &str = "none";
+// Typed instead of an answer to give up the hand, if everybody passes it is a tie
+const PASS_COMMAND: &str = "pass";
+// Typed instead of an answer to stand up from the table and quit
+const LEAVE_COMMAND: &str = "leave";
 
 enum Signal {
     Greet, // This is a self event called every second.
-           // Other signals here,
+    Key(Key),   // read from the keyboard by its own thread
+    Redraw,     // keeps the countdown running between keys
+    Quit,       // Ctrl-C or a signal to end the process
 }
 
-pub fn run(transport: Transport, remote_addr: RemoteAddr) {
-    let mut deck = VisualDeck::new();
-    let default_hand: HandCardData = [ Card{ _type : CardType::Joker, value : 0} ; CARDCOUNT];
-    let mut actual_hand = default_hand;
-    let stdin = io::stdin();
-
-    let mut answer_data = | hand : & HandCardData |{
-        let mut screen = AlternateScreen::from(io::stdout());
-        //write!(screen, "Writing to alternat(iv)e screen!").unwrap();
-        screen.flush().unwrap();
-
-
-        //let mut stdin = termion::async_stdin().keys();
-
-        let mut buffer: AnswerData = [' '; BYTECOUNT];
-
-        let mut opt_answer : Option<String> =  get_command(& mut deck, hand);
-
-        for (i,ch) in opt_answer.unwrap().chars().enumerate() {
-            if i < BYTECOUNT {
-                buffer[i] = ch;
-            } else {
-                break;
-            }
-        }
-        let message = FromClientMessage::TurnAnswer(buffer);
-        bincode::serialize(&message).unwrap()
+// How often the prompt is drawn again while nothing happens
+const REDRAW_EVERY: Duration = Duration::from_millis(250);
 
+// The message for a line the player sent, or why it can not be sent
+fn answer_data(answer: String) -> Result<Vec<u8>, String> {
+    // the server would refuse it, better to say it here and let the player fix it
+    if answer.chars().count() > MAX_ANSWER {
+        return Err(Rejection::TooLong(MAX_ANSWER).to_string());
+    }
+    let message = match answer.trim() {
+        NO_SOLUTION_COMMAND => FromClientMessage::NoSolution,
+        PASS_COMMAND => FromClientMessage::Pass,
+        LEAVE_COMMAND => FromClientMessage::LeaveTable,
+        _ => FromClientMessage::TurnAnswer(answer),
     };
+    Ok(bincode::serialize(&message).unwrap())
+}
 
+// The server answers the handshake with its own before any message,
+// None while it is that one
+fn decode(shaken: &mut bool, input_data: &[u8]) -> Result<Option<FromServerMessage>, String> {
+    if !*shaken {
+        Handshake::check(input_data).map_err(|e| format!("The server can not be used, {}", e))?;
+        *shaken = true;
+        return Ok(None);
+    }
+    bincode::deserialize(input_data).map(Some)
+        .map_err(|e| format!("The server sent a message that can not be read, {}", e))
+}
 
+fn send_handshake<S>(handler: &NodeHandler<S>, server_id: Endpoint) {
+    handler.network().send(server_id, &bincode::serialize(&Handshake::ours()).unwrap());
+}
+
+// Keys and the messages of the server go through the same loop, so the
+// prompt learns at once that the round ended while the player was typing
+pub fn run(transport: Transport, remote_addr: RemoteAddr, table: String, nickname: String, theme: Theme) {
+    let mut deck = VisualDeck::new(theme);
+    let mut prompt = Prompt::new();
+    // the final ranking is on screen until the player presses Enter
+    let mut game_over = false;
+    // why the client stopped, told once the terminal is back to normal
+    let mut farewell: Option<String> = None;
+    let mut shaken = false;
 
     let (handler, listener) = node::split();
 
     let (server_id, local_addr) =
         handler.network().connect(transport, remote_addr.clone()).unwrap();
 
-    listener.for_each(move |event| match event {
+    deck.set_connection(&format!("connecting to {}", remote_addr));
+    // the nickname the server gave, it may not be the one asked for
+    let mut playing_as = nickname.clone();
+
+    let keys = handler.clone();
+    thread::spawn(move || {
+        for key in io::stdin().keys().map_while(Result::ok) {
+            keys.signals().send(Signal::Key(key));
+        }
+    });
+
+    // the loop stops and the terminal is restored as on any other way out
+    let quit = handler.clone();
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP]).unwrap();
+    thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            quit.signals().send(Signal::Quit);
+        }
+    });
+
+    listener.for_each(|event| match event {
         NodeEvent::Network(net_event) => match net_event {
             NetEvent::Connected(_, established) => {
                 if established {
-                    println!("Connected to server at {} by {}", server_id.addr(), transport);
-                    println!("Client identified by local port: {}", local_addr.port());
+                    deck.set_connection(&format!("connected to {}", server_id.addr()));
+                    deck.log(&format!("connected by {}, local port {}", transport, local_addr.port()));
+                    send_handshake(&handler, server_id);
+                    let message = FromClientMessage::Hello { nickname: nickname.clone() };
+                    let output_data = bincode::serialize(&message).unwrap();
+                    handler.network().send(server_id, &output_data);
                     handler.signals().send(Signal::Greet);
+                    handler.signals().send(Signal::Redraw);
                 }
                 else {
-                    println!("Can not connect to server at {} by {}", remote_addr, transport);
+                    farewell = Some(format!("Can not connect to server at {} by {}", remote_addr, transport));
                     handler.stop();
                 }
             }
             NetEvent::Accepted(_, _) => unreachable!(), // Only generated when a listener accepts
             NetEvent::Message(endpoint , input_data) => {
-                let message: FromServerMessage = bincode::deserialize(&input_data).unwrap();
+                let message = match decode(&mut shaken, input_data) {
+                    Ok(Some(message)) => message,
+                    Ok(None) => return,
+                    Err(e) => {
+                        farewell = Some(e);
+                        return handler.stop();
+                    }
+                };
                 match message {
-                    FromServerMessage::Pong(_) => {
-                        let message = FromClientMessage::NewTurn;
+                    FromServerMessage::Welcome { id, nickname } => {
+                        deck.log(&format!("playing as {} (player {})", nickname, id));
+                        playing_as = nickname;
+                        let message = FromClientMessage::JoinTable(table.clone());
                         let output_data = bincode::serialize(&message).unwrap();
                         handler.network().send(endpoint, &output_data);
-                    },
-                    FromServerMessage::UnknownPong => println!("Pong from server"),
+                    }
+                    FromServerMessage::Pong(_) => (),
+                    FromServerMessage::UnknownPong => deck.log("pong from server"),
                     
-                    FromServerMessage::TurnBegin(hand) => {
-                        actual_hand = hand;
-                        handler.network().send(endpoint, & mut answer_data(&hand) );
-
+                    FromServerMessage::TurnBegin { round, hand, target, jokers, countdown } => {
+                        // somebody else at the table started the new game
+                        game_over = false;
+                        deck.set_round(round);
+                        let deadline = countdown.map(|countdown| Deadline::from_countdown(&countdown, Instant::now()));
+                        prompt.begin(hand, target, jokers, deadline);
+                        prompt.draw(&mut deck);
                     },
 
-                    FromServerMessage::TurnContinue => {
-                        let mut screen = AlternateScreen::from(io::stdout());
-                        write!(screen, "turn continue").unwrap();
-                        handler.network().send(endpoint, & mut answer_data(&actual_hand) );
+                    FromServerMessage::TimeLeft(countdown) => {
+                        prompt.set_deadline(Deadline::from_countdown(&countdown, Instant::now()));
+                    }
+
+                    FromServerMessage::SendMsg(msg) => {
+                        deck.draw_message(&msg);
+                        deck.log(&msg);
                     }
 
-                    FromServerMessage::TurnEnd(_) =>
+                    FromServerMessage::Rejected(reasons) => {
+                        let reasons: Vec<String> = reasons.iter().map(Rejection::to_string).collect();
+                        deck.draw_message(&reasons.join(", "));
+                    }
+
+                    FromServerMessage::TurnEnd(end) =>
                         {
-                            let mut screen = AlternateScreen::from(io::stdout());
-                            write!(screen, "turn end!").unwrap();
+                            prompt.close();
+                            let event = match end {
+                                TurnEndType::YouWin => "you won the round".to_string(),
+                                TurnEndType::OtherWin(winner) => format!("{} won the round", winner),
+                                TurnEndType::Tie(pot) => format!("tie, {} cards in the pot", pot),
+                            };
+                            deck.log(&event);
+                            let mut message = format!("turn end! {}", event);
+                            for solution in prompt.solutions(3).iter() {
+                                message += &format!(" {}", solution);
+                            }
+                            deck.draw_message(&message);
                             let message = FromClientMessage::NewTurn;
                             let output_data = bincode::serialize(&message).unwrap();
                             handler.network().send(endpoint, &output_data);
                         },
+
+                    FromServerMessage::TableJoined(name) => {
+                        deck.set_connection(&format!("{} at table {}", playing_as, name));
+                        deck.draw_backs(CARDCOUNT);
+                        let message = FromClientMessage::NewTurn;
+                        let output_data = bincode::serialize(&message).unwrap();
+                        handler.network().send(endpoint, &output_data);
+                    }
+
+                    FromServerMessage::NoSuchTable(name) => {
+                        // nobody plays there yet, so this player opens it
+                        let message = FromClientMessage::CreateTable(name, TableConfig::default());
+                        let output_data = bincode::serialize(&message).unwrap();
+                        handler.network().send(endpoint, &output_data);
+                    }
+
+                    FromServerMessage::TableLeft => handler.stop(),
+
+                    // the screen belongs to the session, the tables go to its log
+                    FromServerMessage::TableList(list) => for info in list.iter() {
+                        deck.log(&format!("{} {} players", info.name, info.players));
+                    },
+
+                    FromServerMessage::Scoreboard(scoreboard) => deck.draw_scoreboard(&scoreboard),
+
+                    FromServerMessage::GameOver { ranking, unclaimed } => {
+                        prompt.close();
+                        game_over = true;
+                        deck.draw_game_over(&ranking, unclaimed);
+                    }
                 }
             }
             NetEvent::Disconnected(_) => {
-                println!("Server is disconnected");
+                farewell = Some("Server is disconnected".to_string());
                 handler.stop();
             }
         },
@@ -112,6 +224,72 @@ pub fn run(transport: Transport, remote_addr: RemoteAddr) {
                 handler.network().send(server_id, &output_data);
                 handler.signals().send_with_timer(Signal::Greet, Duration::from_secs(1));
             }
+            Signal::Key(Key::Ctrl('c')) | Signal::Quit => handler.stop(),
+            Signal::Key(Key::Char('\n')) if game_over => {
+                game_over = false;
+                let output_data = bincode::serialize(&FromClientMessage::NewGame).unwrap();
+                handler.network().send(server_id, &output_data);
+            }
+            Signal::Key(key) => {
+                if let Some(answer) = prompt.key(key) {
+                    match answer_data(answer) {
+                        Ok(output_data) => { handler.network().send(server_id, &output_data); }
+                        Err(e) => deck.draw_message(&e),
+                    }
+                }
+                prompt.draw(&mut deck);
+            }
+            Signal::Redraw => {
+                prompt.draw(&mut deck);
+                handler.signals().send_with_timer(Signal::Redraw, REDRAW_EVERY);
+            }
         },
     });
+
+    drop(deck);
+    if let Some(farewell) = farewell {
+        println!("{}", farewell);
+    }
+}
+
+fn print_tables(list: &[TableInfo]) {
+    for info in list {
+        println!("{:<16} {} players  {:?}", info.name, info.players, info.config);
+    }
+}
+
+pub fn list_tables(transport: Transport, remote_addr: RemoteAddr) {
+    let (handler, listener) = node::split::<()>();
+    let mut shaken = false;
+
+    let (server_id, _) = handler.network().connect(transport, remote_addr.clone()).unwrap();
+
+    listener.for_each(move |event| match event.network() {
+        NetEvent::Connected(_, established) => {
+            if established {
+                send_handshake(&handler, server_id);
+                let output_data = bincode::serialize(&FromClientMessage::ListTables).unwrap();
+                handler.network().send(server_id, &output_data);
+            }
+            else {
+                println!("Can not connect to server at {} by {}", remote_addr, transport);
+                handler.stop();
+            }
+        }
+        NetEvent::Accepted(_, _) => unreachable!(), // Only generated when a listener accepts
+        NetEvent::Message(_, input_data) => {
+            match decode(&mut shaken, input_data) {
+                Ok(Some(FromServerMessage::TableList(list))) => {
+                    print_tables(&list);
+                    handler.stop();
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    println!("{}", e);
+                    handler.stop();
+                }
+            }
+        }
+        NetEvent::Disconnected(_) => handler.stop(),
+    });
 }
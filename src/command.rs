@@ -1,67 +1,114 @@
-use std::cmp::{max, min};
-use std::io;
 use std::io::Write;
-use std::thread;
-use std::time;
+use std::time::{self, Instant};
 
-use termion;
-use termion::input::TermRead;
-use termion::raw::IntoRawMode;
-use crate::common::{AnswerData, BYTECOUNT, HandCardData};
+use termion::event::Key;
+use crate::common::{Countdown, HandCardData, JokerRule};
+use crate::editor::{Edit, LineEditor};
+use crate::answer::{self, Expr, Usage};
+use crate::evaluator::{self, Fraction};
+use crate::solver;
 use crate::terminal::VisualDeck;
 
-pub fn get_command(deck: & mut VisualDeck, hand: &HandCardData) -> Option<String> {
-    // Set terminal to raw mode to allow reading stdin one key at a time
-    let mut stdout = io::stdout().into_raw_mode().unwrap();
+// How long the solutions shown at the end of a round are looked for
+const SOLUTIONS_TIME: time::Duration = time::Duration::from_millis(50);
 
-    // Use asynchronous stdin
-    let mut stdin = termion::async_stdin().keys();
+// When the round ends, as told by the server and counted from our own clock
+#[derive(Clone, Copy)]
+pub struct Deadline {
+    at: Instant,
+    total: u64,
+}
 
-    let mut buffer: AnswerData = [' '; BYTECOUNT];
-    let mut i : usize = 0;
-    loop {
-        if let Some(Ok(key)) = stdin.next() {
-            match key {
-                termion::event::Key::Left => {
-                    i = max(0, i - 1);
-                },
-                termion::event::Key::Right => {
-                    i = min(BYTECOUNT - 1, i + 1);
-                },
-                termion::event::Key::Backspace => {
-                    if i > 0 {
-                        i -= 1;
-                        buffer[i] = ' ';
-                    }
-                },
-                termion::event::Key::Char(char) => {
-                    if char == '\n' {
-                        break;
-                    }
+impl Deadline {
+    pub fn from_countdown(countdown: &Countdown, now: Instant) -> Self {
+        Deadline { at: now + time::Duration::from_secs(countdown.left), total: countdown.total }
+    }
 
-                    if i < BYTECOUNT {
-                        buffer[i] = char;
-                        i += 1;
-                    }
+    fn countdown(&self, now: Instant) -> Countdown {
+        Countdown::new(self.at.saturating_duration_since(now), self.total)
+    }
+}
 
-                }
-                _ => ()
-            }
-        }
-        deck.draw_hand(&hand);
-        write!(
-            stdout,
-            "{}>> {}",
-            termion::cursor::Goto(2, 25),
-            buffer.iter().collect::<String>()
-        )
-            .unwrap();
-        write!( stdout, "{}", termion::cursor::Goto((5 + i) as u16, 25) ). unwrap();
+// The hand on the table and the answer being typed for it; the client feeds it
+// keys and redraws it as they come, between the messages of the server
+#[derive(Default)]
+pub struct Prompt {
+    editor: LineEditor,         // lives across rounds to keep the history
+    hand: HandCardData,
+    target: i64,
+    jokers: JokerRule,
+    deadline: Option<Deadline>,
+    open: bool,                 // false between rounds and once the time is out
+    // what the answer takes and makes, worked out again only when it changes
+    usage: Usage,
+    value: Option<Fraction>,
+}
+
+impl Prompt {
+    pub fn new() -> Self {
+        Prompt { editor: LineEditor::new(), ..Prompt::default() }
+    }
+
+    pub fn begin(&mut self, hand: HandCardData, target: i64, jokers: JokerRule, deadline: Option<Deadline>) {
+        self.hand = hand;
+        self.target = target;
+        self.jokers = jokers;
+        self.deadline = deadline;
+        self.open = true;
+        self.refresh();
+    }
+
+    pub fn set_deadline(&mut self, deadline: Deadline) {
+        self.deadline = Some(deadline);
+    }
+
+    // The round is over, keys do nothing until the next one
+    pub fn close(&mut self) {
+        self.open = false;
+        self.deadline = None;
+    }
 
+    // Some of the ways the hand could be made, as many as come out in a moment:
+    // the keys and the server wait while they are looked for
+    pub fn solutions(&self, limit: usize) -> Vec<Expr> {
+        let deadline = Instant::now() + SOLUTIONS_TIME;
+        solver::solve(&self.hand, self.target, self.jokers, limit, Some(deadline))
+    }
+
+    fn refresh(&mut self) {
+        let typed = self.editor.line();
+        self.usage = answer::card_usage(&typed, &self.hand, self.jokers);
+        self.value = evaluator::running_value(&typed);
+    }
 
-        stdout.lock().flush().unwrap();
+    // The line typed when the key is Enter
+    pub fn key(&mut self, key: Key) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+        let edit = self.editor.key(key);
+        self.refresh();
+        match edit {
+            Edit::Submit(line) => Some(line),
+            Edit::Editing => None,
+        }
+    }
 
-        thread::sleep(time::Duration::from_millis(50));
+    // Out of time the prompt closes, the server ends the round for everybody
+    pub fn draw(&mut self, deck: &mut VisualDeck) {
+        if !self.open {
+            return;
+        }
+        deck.draw_hand(&self.hand, &self.usage.used);
+        deck.draw_target(self.target, self.jokers);
+        if let Some(deadline) = self.deadline {
+            let countdown = deadline.countdown(Instant::now());
+            deck.draw_countdown(&countdown);
+            if countdown.left == 0 {
+                self.open = false;
+            }
+        }
+        deck.draw_prompt(&self.editor.line(), self.editor.cursor(), &self.usage.unmatched, self.value);
+        deck.stdout.flush().unwrap();
     }
-    Some(buffer.iter().collect())
 }
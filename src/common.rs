@@ -1,4 +1,8 @@
 use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::decks;
+use crate::evaluator::Fraction;
 
 extern crate serde;
 extern crate bincode;
@@ -10,45 +14,315 @@ pub enum CardType {
     Club,
     Sword,
     Cup,
-    Joker
+    Joker,
+    Heart,
+    Diamond,
+    Clover,     // the clubs of the French deck, `Club` is the Spanish one
+    Spade,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Debug)]
 pub struct Card {
     pub(crate) _type: CardType,
-    pub(crate) value: u8
+    pub(crate) value: u8,
+    pub(crate) worth: u8,   // what it counts in the answers, usually its value
+}
+
+impl Card {
+    pub fn new(_type: CardType, value: u8) -> Self {
+        Card { _type, value, worth: value }
+    }
+
+    pub fn with_worth(_type: CardType, value: u8, worth: u8) -> Self {
+        Card { _type, value, worth }
+    }
+}
+
+// Characters of the longest answer the server checks
+pub const MAX_ANSWER: usize = 128;
+
+pub(crate) const CARDCOUNT: usize = 4;      // cards in a hand unless the table says otherwise
+pub(crate) const MIN_CARDCOUNT: usize = 3;
+pub(crate) const MAX_CARDCOUNT: usize = 6;
+pub type HandCardData = Vec<Card>;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum UnsolvablePolicy {
+    Redeal,     // hands without solution go back to the deck before anyone sees them
+    Claim,      // players say "no solution" and the server checks it
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum JokerRule {
+    #[default]
+    Excluded,   // the jokers are left out of the deck
+    Wild(u8),   // the player gives each joker a value up to this one in the answer, as `J=7`
+    Fixed(u8),  // a joker is written as this number
 }
 
-pub const BYTECOUNT: usize = 32;
-pub type AnswerData = [char; BYTECOUNT];
+impl JokerRule {
+    // The values a wild joker can be given, the ones of the other cards
+    // of the deck, none when the jokers are not wild
+    pub fn wild_values(&self) -> std::ops::RangeInclusive<i64> {
+        let high = match self {
+            JokerRule::Wild(high) => *high as i64,
+            _ => 0,
+        };
+        1..=high
+    }
 
-pub(crate) const CARDCOUNT: usize = 4;
-pub type HandCardData = [Card; CARDCOUNT];
+    // What the card is worth when written as a number, None for a wild
+    // joker because the player binds it in the answer instead
+    pub fn face_value(&self, card: &Card) -> Option<i64> {
+        match (card._type, self) {
+            (CardType::Joker, JokerRule::Wild(_)) => None,
+            (CardType::Joker, JokerRule::Fixed(value)) => Some(*value as i64),
+            _ => Some(card.worth as i64),
+        }
+    }
+}
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DeckKind {
+    Spanish,
+    French,
+    Custom { name: String, cards: Vec<Card> },   // read from a deck file by the server
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableConfig {
+    pub unsolvable: UnsolvablePolicy,
+    pub round_secs: Option<u64>,    // a round nobody wins in time is a tie
+    pub target: i64,                // the number the answers have to make
+    pub hand_size: usize,           // between MIN_CARDCOUNT and MAX_CARDCOUNT
+    pub jokers: JokerRule,
+    pub deck: DeckKind,
+}
+
+pub const DEFAULT_TARGET: i64 = 24;
+// The targets go from -MAX_TARGET to MAX_TARGET
+pub const MAX_TARGET: i64 = 1000;
+
+impl TableConfig {
+    // Why a table can not be played with this configuration, checked on
+    // the server because a client can send anything
+    pub fn check(&self) -> Result<(), String> {
+        if !(MIN_CARDCOUNT..=MAX_CARDCOUNT).contains(&self.hand_size) {
+            return Err(format!("hands have between {} and {} cards", MIN_CARDCOUNT, MAX_CARDCOUNT));
+        }
+        if !(-MAX_TARGET..=MAX_TARGET).contains(&self.target) {
+            return Err(format!("the target has to be between {} and {}", -MAX_TARGET, MAX_TARGET));
+        }
+        match self.jokers {
+            JokerRule::Fixed(value) if value > decks::MAX_VALUE => {
+                return Err(format!("a joker can not be worth more than {}", decks::MAX_VALUE));
+            }
+            JokerRule::Wild(high) if !(1..=decks::MAX_VALUE).contains(&high) => {
+                return Err(format!("a wild joker takes values between 1 and {}", decks::MAX_VALUE));
+            }
+            _ => (),
+        }
+        let cards = decks::cards(&self.deck);
+        decks::check(&cards)?;
+        let in_play = cards.iter()
+            .filter(|card| self.jokers != JokerRule::Excluded || card._type != CardType::Joker)
+            .count();
+        if in_play < self.hand_size {
+            return Err(format!("the deck has {} cards to play, less than a hand", in_play));
+        }
+        Ok(())
+    }
+}
+
+impl Default for TableConfig {
+    fn default() -> Self {
+        TableConfig {
+            unsolvable: UnsolvablePolicy::Redeal,
+            round_secs: None,
+            target: DEFAULT_TARGET,
+            hand_size: CARDCOUNT,
+            jokers: JokerRule::Excluded,
+            deck: DeckKind::Spanish,
+        }
+    }
+}
+
+// Seconds left of the round, as the server counts them
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Countdown {
+    pub left: u64,
+    pub total: u64,
+}
+
+impl Countdown {
+    // The time left rounded up, so the last second still shows as 1
+    pub fn new(left: std::time::Duration, total: u64) -> Self {
+        let left = left.as_secs() + if left.subsec_nanos() > 0 { 1 } else { 0 };
+        Countdown { left, total }
+    }
+}
+
+pub const MAX_NICKNAME: usize = 16;
+pub const MAX_TABLE_NAME: usize = 16;
+
+// The table every server opens at start and the one players join by default
+pub const DEFAULT_TABLE: &str = "main";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableInfo {
+    pub name: String,
+    pub players: usize,
+    pub config: TableConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScoreEntry {
+    pub rank: usize,        // players with the same cards share the rank
+    pub nickname: String,
+    pub cards: usize,
+}
+
+// Both sides send it first on a connection, before any message. It is decoded
+// on its own, so peers of another version are told apart instead of misread
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Handshake {
+    pub magic: [u8; 4],
+    pub version: u16,
+}
+
+const PROTOCOL_MAGIC: [u8; 4] = *b"C24G";
+// The magic and the version, bincode adds nothing around them
+const HANDSHAKE_LEN: usize = 6;
+// The first released protocol. Any change to the messages below after a
+// release needs a new version
+pub const PROTOCOL_VERSION: u16 = 1;
+
+impl Handshake {
+    pub fn ours() -> Self {
+        Handshake { magic: PROTOCOL_MAGIC, version: PROTOCOL_VERSION }
+    }
+
+    // Why we can not talk with the peer that sent these bytes
+    pub fn check(data: &[u8]) -> Result<(), String> {
+        match bincode::deserialize::<Handshake>(data) {
+            Ok(theirs) if theirs.magic == PROTOCOL_MAGIC && data.len() == HANDSHAKE_LEN => {
+                if theirs.version == PROTOCOL_VERSION {
+                    Ok(())
+                } else {
+                    Err(format!("protocol version {} where {} is spoken here", theirs.version, PROTOCOL_VERSION))
+                }
+            }
+            _ => Err("not a cardascii-24game peer".to_string()),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub enum FromClientMessage {
+    Hello { nickname: String },         // first message after the handshake, before joining any table
     Ping,
     NewTurn,
-    TurnAnswer(usize, AnswerData),
+    TurnAnswer(String),     // at most MAX_ANSWER characters
+    NoSolution,
+    Pass,                               // gives up the hand, all passing makes a tie
+    ListTables,
+    CreateTable(String, TableConfig),   // the creator joins the new table
+    JoinTable(String),
+    LeaveTable,
+    NewGame,                // after a game over, deals a new deck to the table
 }
 
 #[derive(Serialize, Deserialize)]
 pub enum TurnEndType {
-    Tie,
+    Tie(usize),         // cards in the pot for the next winner
     YouWin,
-    OtherWin
+    OtherWin(String),   // nickname of the winner
+}
+
+// Why the server did not take an answer, told only to the player who sent it
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub enum Rejection {
+    Syntax(usize),              // 1-based column where the answer stops making sense
+    WrongResult(Fraction),      // what the answer makes instead of the target
+    UnusedCards(Vec<Card>),
+    ReusedCards(Vec<Card>),     // written more times than dealt
+    NotInHand(Vec<i64>),
+    NoWildJoker(Vec<i64>),      // joker values with no wild joker to take them
+    DivisionByZero,
+    Overflow,
+    TooLong(usize),             // the most characters an answer can have
+    TooLate,
+}
+
+fn worths(cards: &[Card]) -> Vec<u8> {
+    cards.iter().map(|card| card.worth).collect()
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejection::Syntax(column) => write!(f, "syntax error at column {}", column),
+            Rejection::WrongResult(result) => write!(f, "your answer gives {}", result),
+            Rejection::UnusedCards(cards) => write!(f, "unused cards {:?}", worths(cards)),
+            Rejection::ReusedCards(cards) => write!(f, "cards used more than once {:?}", worths(cards)),
+            Rejection::NotInHand(numbers) => write!(f, "numbers not in the hand {:?}", numbers),
+            Rejection::NoWildJoker(values) => write!(f, "jokers that can not be used {:?}", values),
+            Rejection::DivisionByZero => write!(f, "division by zero"),
+            Rejection::Overflow => write!(f, "number too big"),
+            Rejection::TooLong(max) => write!(f, "the answer is too long, at most {} characters", max),
+            Rejection::TooLate => write!(f, "too late, the round is over"),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub enum FromServerMessage {
 
+    Welcome { id: usize, nickname: String },    // the nickname may be changed to be unique
     Pong(usize),            // Used for connection oriented protocols
     UnknownPong,            // Used for non-connection oriented protocols
     TurnEnd(TurnEndType),             // Used for bring a good notice
-    SendMsg(str),
-    TurnContinue,               // Used for bring a bad notice for all
-    TurnBegin(HandCardData),   // Used for bring the cards
+    SendMsg(String),
+    Rejected(Vec<Rejection>),   // the answer sent was not taken, the round goes on
+    TurnBegin { round: u8, hand: HandCardData, target: i64, jokers: JokerRule, countdown: Option<Countdown> },   // Used for bring the cards
+    TimeLeft(Countdown),
+    TableList(Vec<TableInfo>),
+    TableJoined(String),
+    TableLeft,
+    NoSuchTable(String),
+    Scoreboard(Vec<ScoreEntry>),
+    GameOver { ranking: Vec<ScoreEntry>, unclaimed: usize },   // unclaimed: cards nobody won
 
     
 }
+
+#[test]
+fn handshake_test() {
+    let ours = bincode::serialize(&Handshake::ours()).unwrap();
+    assert_eq!(Handshake::check(&ours), Ok(()));
+    let newer = bincode::serialize(&Handshake { version: PROTOCOL_VERSION + 1, ..Handshake::ours() }).unwrap();
+    assert_eq!(Handshake::check(&newer), Err(format!("protocol version {} where {} is spoken here", PROTOCOL_VERSION + 1, PROTOCOL_VERSION)));
+    // a client from before the handshake says hello first
+    let hello = bincode::serialize(&FromClientMessage::Hello { nickname: "ana".to_string() }).unwrap();
+    assert!(Handshake::check(&hello).is_err());
+    assert!(Handshake::check(b"C2").is_err());
+}
+
+#[test]
+fn config_check_test() {
+    assert_eq!(TableConfig::default().check(), Ok(()));
+    assert!(TableConfig { hand_size: 7, ..TableConfig::default() }.check().is_err());
+    assert!(TableConfig { target: MAX_TARGET + 1, ..TableConfig::default() }.check().is_err());
+    assert!(TableConfig { jokers: JokerRule::Fixed(200), ..TableConfig::default() }.check().is_err());
+    assert!(TableConfig { jokers: JokerRule::Wild(0), ..TableConfig::default() }.check().is_err());
+    assert!(TableConfig { jokers: JokerRule::Wild(14), ..TableConfig::default() }.check().is_err());
+    assert_eq!(TableConfig { jokers: JokerRule::Wild(13), deck: DeckKind::French, ..TableConfig::default() }.check(), Ok(()));
+    let custom = |cards| TableConfig { deck: DeckKind::Custom { name: "mine".to_string(), cards }, ..TableConfig::default() };
+    assert_eq!(custom(vec![]).check(), Err("the deck has no cards".to_string()));
+    assert!(custom(vec![Card::new(CardType::Cup, 1); decks::MAX_DECK + 1]).check().is_err());
+    assert!(custom(vec![Card::new(CardType::Cup, 200); 10]).check().is_err());
+    // the jokers are left out, three cups do not make a hand of four
+    let mut cards = vec![Card::new(CardType::Cup, 1); 3];
+    cards.push(Card::new(CardType::Joker, 1));
+    assert_eq!(custom(cards).check(), Err("the deck has 3 cards to play, less than a hand".to_string()));
+}
@@ -1,38 +1,25 @@
-extern crate termion;
-
-use std::io::{stdin, Stdin, stdout, Write};
 use rand::prelude::SliceRandom;
 use rand::thread_rng;
-use rcalc::{Lexer, Token};
-
-
-use super::common::{Card, CardType};
-use self::termion::input::{MouseTerminal, TermRead};
-use self::termion::raw::{IntoRawMode, RawTerminal};
-
-const CARD_ID_JOCKER_1: u8 = 0;
-const CARD_ID_JOCKER_2: u8 = 1;
-
-
-/*macro_rules! make_str_card {
-    ( $x:expr $( , $more:expr )* ) => (
-        format!("{}{}{}{}{}{}{}{}{}", $x, $( $more ),* )
-    )
-}*/
-
-macro_rules! make_str_card {
-    ( $( $x:expr ),* ) => {
-        {
-            let mut vec = Vec::<&'static str>::new();
-            $(
-                #[allow(unused_assignments)]
-                {
-                    vec.push($x);
-                }
-            )*
-            vec
-        }
-    };
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::common::{Card, CardType, DeckKind, JokerRule, Rejection, TableConfig, UnsolvablePolicy};
+use crate::decks;
+use crate::answer::{self, CardsError};
+use crate::evaluator::{self, EvalError, Fraction};
+use crate::solver;
+
+// How many times an unsolvable hand is shuffled back before giving up the deck
+const MAX_REDEALS: usize = 32;
+// The server does nothing else while it looks for a hand with a solution
+const REDEAL_TIME: Duration = Duration::from_millis(500);
+
+// What came out of dealing a hand
+#[derive(PartialEq, Eq, Debug)]
+pub enum Deal {
+    Dealt,
+    Unchecked,  // no hand with a solution came out in time, this one can be claimed
+    Exhausted,  // less cards left than a hand, the game is over
 }
 
 struct Deck{
@@ -40,63 +27,48 @@ struct Deck{
 }
 
 impl Deck {
-    fn new() -> Self {
-
-        //static mut VISUAL_CARDS : HashMap<Card, Vec<&'static str>> = HashMap::<Card, Vec<&'static str>>::new();
-
-        let mut me = Deck {
-            cards : Vec::<Card>::new(),
-        };
-
-        load_cards(& mut me);
-
-        me
-    }
-
-    fn add(&mut self, _type: CardType, value: u8) {
-        self.cards.push( Card{ _type, value } );
+    fn new(kind: &DeckKind) -> Self {
+        Deck {
+            cards : decks::cards(kind),
+        }
     }
 
     fn as_ids(& self) -> Vec<u8> {
-        (0 .. self.cards.len() as u8).collect()
+        (0 ..= u8::MAX).take(self.cards.len()).collect()
     }
 
     fn as_ids_no_jokers(& self) -> Vec<u8> {
-        (0 .. self.cards.len() as u8).collect()
+        self.as_ids().into_iter()
+            .filter(|id| self.cards[*id as usize]._type != CardType::Joker)
+            .collect()
     }
 
     fn get_card(& self, id: & u8) -> Option<&Card> {
         self.cards.get(*id as usize)
     }
 
-    fn get_card_pos(& self, i: usize, card_ids : & Vec<u8>) -> Option<&Card> {
-        match card_ids.get(i) {
-            Some(id)    => self.get_card(id),
-            None            => None
-        }
-    }
-
     fn get_cards_from_stack(& self, stack: & CardStack) -> Vec<&Card> {
-        stack.card_ids.iter().map( |card_id| self.get_card(card_id) ).collect()
+        stack.card_ids.iter().filter_map( |card_id| self.get_card(card_id) ).collect()
     }
 
 }
 
 pub struct CardStack {
-    is_face_up:bool,
     card_ids: Vec<u8>,
 }
 
 impl CardStack {
-    fn new(is_face_up: bool) -> Self {
+    fn new() -> Self {
         CardStack {
-            is_face_up,
             card_ids: Vec::<u8>::new()
         }
     }
 
-    fn add_cards(&mut self, deck: &Deck) {
-        self.card_ids = deck.as_ids_no_jokers();
+    fn add_cards(&mut self, deck: &Deck, jokers: JokerRule) {
+        self.card_ids = match jokers {
+            JokerRule::Excluded => deck.as_ids_no_jokers(),
+            _ => deck.as_ids(),
+        };
     }
 
     fn add_all_from(&mut self, from: &mut CardStack) {
@@ -132,6 +104,10 @@ impl CardStack {
         self.card_ids.is_empty()
     }
 
+    fn len(&self) -> usize {
+        self.card_ids.len()
+    }
+
 }
 
 pub struct Game24{
@@ -141,66 +117,95 @@ pub struct Game24{
     visible_cards:      CardStack,
     players_cards:      Vec<CardStack>,
     accumulate_cards:   CardStack,
-    operation:          String,
-    turn_num:           u8
+    turn_num:           u8,
+    unchecked:          bool,   // the hand on the table may have no solution
+    solvable:           Option<bool>,   // worked out once per hand, at the deal or the first claim
+    claimed_by:         Vec<usize>,     // seats that claimed this hand, one claim each
+    config:             TableConfig,
 }
 #[derive(PartialEq)]
 pub enum TurnResult {
     Winner(usize),
     Tie,
-    Gaming,
-    Abandoned
 }
 
-impl Game24 {
-    pub(crate) fn new(/*player: u8, deck: &'a Deck*/) -> Self {
-        let mut hidden_cards = CardStack::new(false);
-        let mut deck = Deck::new();
-        hidden_cards.add_cards(&deck);
-        hidden_cards.shuffle();
-        let mut players_cards = Vec::<CardStack>::new();
+pub enum AnswerError {
+    Eval(EvalError),
+    WrongResult(Fraction),
+    Cards(CardsError),
+}
 
-        players_cards.push(CardStack::new(false));
-        players_cards.push(CardStack::new(false));
+impl AnswerError {
+    // What the player who sent the answer is told
+    pub fn rejections(&self) -> Vec<Rejection> {
+        match self {
+            AnswerError::Eval(EvalError::Syntax(column)) => vec![Rejection::Syntax(*column)],
+            AnswerError::Eval(EvalError::DivisionByZero) => vec![Rejection::DivisionByZero],
+            AnswerError::Eval(EvalError::Overflow) => vec![Rejection::Overflow],
+            AnswerError::WrongResult(result) => vec![Rejection::WrongResult(*result)],
+            AnswerError::Cards(e) => e.rejections(),
+        }
+    }
+}
 
+impl fmt::Display for AnswerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reasons: Vec<String> = self.rejections().iter().map(Rejection::to_string).collect();
+        write!(f, "{}", reasons.join(", "))
+    }
+}
+
+pub enum Claim {
+    Accepted,   // the hand had no solution, the claimer takes it
+    Rejected,   // there was a solution, the claimer pays a card to the pot
+    NotAllowed,
+    Repeated,   // the claimer already tried this hand
+}
+
+impl Game24 {
+    pub(crate) fn new(config: TableConfig) -> Self {
+        let mut hidden_cards = CardStack::new();
+        let deck = Deck::new(&config.deck);
+        hidden_cards.add_cards(&deck, config.jokers);
+        hidden_cards.shuffle();
         Game24 {
             deck,
             hidden_cards,
-            visible_cards:  CardStack::new(true),
-            players_cards,
-            accumulate_cards:  CardStack::new(false),
-            operation:      "24".to_string(),
-            turn_num: 0
+            visible_cards:  CardStack::new(),
+            players_cards:  Vec::<CardStack>::new(),
+            accumulate_cards:  CardStack::new(),
+            turn_num: 0,
+            unchecked: false,
+            solvable: None,
+            claimed_by: Vec::new(),
+            config,
         }
     }
 
-    fn reset(&mut self) {
-        self.hidden_cards.add_all_from( &mut self.visible_cards );
-        for player_cards in & mut self.players_cards.iter_mut() {
-            self.hidden_cards.add_all_from( player_cards );
-        }
-
-        self.hidden_cards.shuffle();
+    // Every player gets its own stack for the cards it wins, the index is its seat
+    pub fn add_player(&mut self) -> usize {
+        self.players_cards.push(CardStack::new());
+        self.players_cards.len() - 1
     }
 
-    fn play(&mut self) -> TurnResult {
-        let mut stdin = stdin();
-        let mut stdout = MouseTerminal::from(stdout().into_raw_mode().unwrap());
+    // Cards left by ties, the next winner takes them
+    pub fn pot(&self) -> usize {
+        self.accumulate_cards.len()
+    }
 
-        stdout.flush().unwrap();
+    // Cards won by each seat
+    pub fn cards_won(&self) -> Vec<usize> {
+        self.players_cards.iter().map(CardStack::len).collect()
+    }
 
-        let mut result : TurnResult = TurnResult::Gaming;
-      /*  loop {
-            result = self.turn();
-            if result != GameResult::Gaming {
-                break;
-            }
-        }*/
-        result
+    // Cards left without owner once the deck cannot give another hand:
+    // what remains in the deck, a pot left by ties and an unsolved hand
+    pub fn unclaimed(&self) -> usize {
+        self.hidden_cards.len() + self.accumulate_cards.len() + self.visible_cards.len()
     }
 
-    pub fn get_gived_card(&self, i: usize) -> Option<&Card>{
-        self.deck.get_card_pos(i, &self.visible_cards.card_ids)
+    pub fn turn_num(&self) -> u8 {
+        self.turn_num
     }
 
     pub fn end_turn(&mut self, result: TurnResult) {
@@ -212,136 +217,226 @@ impl Game24 {
             }
             TurnResult::Tie =>
                 self.accumulate_cards.add_all_from(&mut self.visible_cards),
-            _ => ()
         }
+        self.unchecked = false;
+        self.solvable = None;
+        self.claimed_by.clear();
     }
-    pub fn give_cards(&mut self) -> bool{
-        !self.hidden_cards.is_empty() &&
-            self.visible_cards.add_n_from(&mut self.hidden_cards, 4)
-    }
-
-    fn play_turn(&mut self, stdin: &mut Stdin) -> TurnResult {
-        let mut turn = TurnResult::Gaming;
-        /*while turn == GameResult::Gaming {
-            for c in stdin.events() {
-                let evt = c.unwrap();
-                match evt {
-                    Event::Key(Key()::Char('r')) => {
-                        match self.resolve_operation() {
-                            Some(op) =>
-                                turn = if op == 24
-                                { GameResult::Win } else
-                                { GameResult::Lose },
-                            None =>
-                                turn = GameResult::Tie
-                        };
-                        break;
-                    },
-                    _ => {}
+    pub fn give_cards(&mut self) -> Deal {
+        if !self.visible_cards.is_empty() {
+            // a hand is already on the table
+            return if self.unchecked { Deal::Unchecked } else { Deal::Dealt };
+        }
+        if self.hidden_cards.len() < self.config.hand_size {
+            return Deal::Exhausted;
+        }
+        let size = self.config.hand_size as u8;
+        self.turn_num += 1;
+        if self.config.unsolvable == UnsolvablePolicy::Claim {
+            self.visible_cards.add_n_from(&mut self.hidden_cards, size);
+            return Deal::Dealt;
+        }
+        let deadline = Instant::now() + REDEAL_TIME;
+        for _ in 0..MAX_REDEALS {
+            self.visible_cards.add_n_from(&mut self.hidden_cards, size);
+            match solver::has_solution_before(&self.hand(), self.target(), self.config.jokers, Some(deadline)) {
+                Some(true) => {
+                    self.solvable = Some(true);
+                    return Deal::Dealt;
+                }
+                Some(false) => {
+                    self.hidden_cards.add_all_from(&mut self.visible_cards);
+                    self.hidden_cards.shuffle();
                 }
+                None => break,
             }
-        }*/
-        turn
+        }
+        // no luck in time, the players get a hand that may have no solution
+        if self.visible_cards.is_empty() {
+            self.visible_cards.add_n_from(&mut self.hidden_cards, size);
+        }
+        self.unchecked = true;
+        Deal::Unchecked
     }
 
-    pub fn make_answer(&mut self, user: usize, answer: String) -> Result<(), Err<str> > {
-        let mut lexer = Lexer::from(answer.as_str());
+    pub fn hand(&self) -> Vec<Card> {
+        self.deck.get_cards_from_stack(&self.visible_cards).into_iter().copied().collect()
+    }
 
-        let mut cards_vec   = self.deck.get_cards_from_stack(visible_cards);
+    // The number every answer has to make, set per table
+    pub fn target(&self) -> i64 {
+        self.config.target
+    }
 
-        while let Ok(token) =  lexer.next_token() {
-            if token == Token::EOF {
-                break;
-            }
-            if let Token::NUMBER(n)  = token {
-                print!("num : {} => ", n);
-
-                if let Some(i) = cards_vec.iter().position( |x| *x == n ) {
-                    cards_vec.remove(i);
-                    println!("use a card!");
-                } else {
-                    println!("don't use a card :(");
-                }
-            }
+    // The solver runs at most once per hand, whoever claims and however often
+    pub fn claim_no_solution(&mut self, user: usize) -> Claim {
+        let allowed = self.config.unsolvable == UnsolvablePolicy::Claim || self.unchecked;
+        if !allowed || self.visible_cards.is_empty() {
+            return Claim::NotAllowed;
+        }
+        if self.claimed_by.contains(&user) {
+            return Claim::Repeated;
         }
-        if cards_vec.is_empty() {
-            game.end_turn(TurnResult::Winner(0));
-            Result::Ok(())
+        self.claimed_by.push(user);
+        let solvable = match self.solvable {
+            Some(solvable) => solvable,
+            None => solver::has_solution(&self.hand(), self.target(), self.config.jokers),
+        };
+        self.solvable = Some(solvable);
+        if solvable {
+            self.accumulate_cards.add_one_from(&mut self.players_cards[user]);
+            Claim::Rejected
         } else {
-            Result::Err("don't use this cards {cards_vec:?}")
+            self.end_turn(TurnResult::Winner(user));
+            Claim::Accepted
         }
     }
 
-    fn resolve_operation(& self) -> Option<u16> {
-        match self.operation.parse::<u16>() {
-            Ok(op) => Some(op),
-            Err(_) => None
+    pub fn make_answer(&mut self, user: usize, answer: &str) -> Result<(), AnswerError> {
+        let expr = answer::parse(answer)
+            .map_err(|column| AnswerError::Eval(EvalError::Syntax(column)))?;
+
+        answer::check_cards(&expr, &self.hand(), self.config.jokers).map_err(AnswerError::Cards)?;
+
+        let result = evaluator::eval(&expr).map_err(AnswerError::Eval)?;
+        if result != Fraction::from(self.target()) {
+            return Err(AnswerError::WrongResult(result));
         }
+
+        self.end_turn(TurnResult::Winner(user));
+        Ok(())
     }
 
 }
 
-fn main() {
+#[test]
+fn redeal_test() {
+    let mut game = Game24::new(TableConfig::default());
+    game.add_player();
+    let mut deal = game.give_cards();
+    while deal != Deal::Exhausted {
+        if deal == Deal::Dealt {
+            assert!(solver::has_solution(&game.hand(), game.target(), game.config.jokers));
+            assert!(matches!(game.claim_no_solution(0), Claim::NotAllowed));
+        }
+        game.end_turn(TurnResult::Winner(0));
+        deal = game.give_cards();
+    }
+    assert!(game.turn_num() > 0);
+    assert_eq!(game.cards_won().iter().sum::<usize>() + game.unclaimed(), game.deck.as_ids_no_jokers().len());
+}
+
+#[test]
+fn claim_test() {
+    let mut game = Game24::new(TableConfig { unsolvable: UnsolvablePolicy::Claim, ..TableConfig::default() });
+    game.add_player();
+    game.add_player();
+    while game.give_cards() != Deal::Exhausted {
+        let solvable = solver::has_solution(&game.hand(), game.target(), game.config.jokers);
+        match game.claim_no_solution(1) {
+            Claim::Accepted => assert!(!solvable),
+            Claim::Rejected => {
+                assert!(solvable);
+                // one claim per seat and hand, the other seat may still try
+                assert!(matches!(game.claim_no_solution(1), Claim::Repeated));
+                assert!(matches!(game.claim_no_solution(0), Claim::Rejected));
+                game.end_turn(TurnResult::Winner(0));
+            }
+            Claim::NotAllowed | Claim::Repeated => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn pot_test() {
+    use crate::common::CARDCOUNT;
+    let mut game = Game24::new(TableConfig::default());
+    game.add_player();
+    game.add_player();
+    assert_eq!(game.give_cards(), Deal::Dealt);
+    game.end_turn(TurnResult::Tie);
+    assert_eq!(game.pot(), CARDCOUNT);
+    assert_eq!(game.give_cards(), Deal::Dealt);
+    game.end_turn(TurnResult::Winner(1));
+    assert_eq!(game.pot(), 0);
+    assert_eq!(game.cards_won(), vec![0, 2 * CARDCOUNT]);
+}
+
+#[test]
+fn target_test() {
+    let mut game = Game24::new(TableConfig { target: 10, ..TableConfig::default() });
+    game.add_player();
+    assert_eq!(game.give_cards(), Deal::Dealt);
+    assert!(solver::has_solution(&game.hand(), 10, game.config.jokers));
+    let answer = solver::solve(&game.hand(), 10, game.config.jokers, 1, None)[0].to_string();
+    assert!(game.make_answer(0, &answer).is_ok());
+}
+
+#[test]
+fn hand_size_test() {
+    for hand_size in [3, 6] {
+        let mut game = Game24::new(TableConfig { hand_size, ..TableConfig::default() });
+        game.add_player();
+        assert_eq!(game.give_cards(), Deal::Dealt);
+        assert_eq!(game.hand().len(), hand_size);
+        assert!(solver::has_solution(&game.hand(), game.target(), game.config.jokers));
+    }
+}
+
+#[test]
+fn jokers_test() {
+    let jokers_in = |game: &Game24| game.hidden_cards.card_ids.iter()
+        .filter(|id| game.deck.cards[**id as usize]._type == CardType::Joker).count();
+    assert_eq!(jokers_in(&Game24::new(TableConfig::default())), 0);
+    let game = Game24::new(TableConfig { jokers: JokerRule::Wild(12), ..TableConfig::default() });
+    assert_eq!(jokers_in(&game), 2);
+}
 
-    /*let mut game = Game24::new(0, &deck);
-    game.play();*/
+#[test]
+fn french_deck_test() {
+    let mut game = Game24::new(TableConfig { deck: DeckKind::French, ..TableConfig::default() });
+    game.add_player();
+    while game.give_cards() != Deal::Exhausted {
+        game.end_turn(TurnResult::Winner(0));
+    }
+    assert_eq!(game.cards_won().iter().sum::<usize>() + game.unclaimed(), 52);
 }
 
-fn load_cards(deck: & mut Deck) {
-
-    deck.add(CardType::Joker, 0);
-    deck.add(CardType::Joker, 0);
-
-    deck.add(CardType::Sword, 12);
-    deck.add(CardType::Sword, 11);
-    deck.add(CardType::Sword, 10);
-    deck.add(CardType::Sword, 9);
-    deck.add(CardType::Sword, 8);
-    deck.add(CardType::Sword, 7);
-    deck.add(CardType::Sword, 6);
-    deck.add(CardType::Sword, 5);
-    deck.add(CardType::Sword, 4);
-    deck.add(CardType::Sword, 3);
-    deck.add(CardType::Sword, 2);
-    deck.add(CardType::Sword, 1);
-
-    deck.add(CardType::Club, 12);
-    deck.add(CardType::Club, 11);
-    deck.add(CardType::Club, 10);
-    deck.add(CardType::Club, 9);
-    deck.add(CardType::Club, 8);
-    deck.add(CardType::Club, 7);
-    deck.add(CardType::Club, 6);
-    deck.add(CardType::Club, 5);
-    deck.add(CardType::Club, 4);
-    deck.add(CardType::Club, 3);
-    deck.add(CardType::Club, 2);
-    deck.add(CardType::Club, 1);
-
-    deck.add(CardType::Gold, 12);
-    deck.add(CardType::Gold, 11);
-    deck.add(CardType::Gold, 10);
-    deck.add(CardType::Gold, 9);
-    deck.add(CardType::Gold, 8);
-    deck.add(CardType::Gold, 7);
-    deck.add(CardType::Gold, 6);
-    deck.add(CardType::Gold, 5);
-    deck.add(CardType::Gold, 4);
-    deck.add(CardType::Gold, 3);
-    deck.add(CardType::Gold, 2);
-    deck.add(CardType::Gold, 1);
-
-    deck.add(CardType::Cup, 12);
-    deck.add(CardType::Cup, 11);
-    deck.add(CardType::Cup, 10);
-    deck.add(CardType::Cup, 9);
-    deck.add(CardType::Cup, 8);
-    deck.add(CardType::Cup, 7);
-    deck.add(CardType::Cup, 6);
-    deck.add(CardType::Cup, 5);
-    deck.add(CardType::Cup, 4);
-    deck.add(CardType::Cup, 3);
-    deck.add(CardType::Cup, 2);
-    deck.add(CardType::Cup, 1);
+#[test]
+fn rejections_test() {
+    let mut game = Game24::new(TableConfig::default());
+    game.add_player();
+    assert_eq!(game.give_cards(), Deal::Dealt);
+    let hand = game.hand();
+    let numbers: Vec<String> = hand.iter().map(|card| card.worth.to_string()).collect();
+    let mut rejections = |answer: &str| game.make_answer(0, answer).err().map(|e| e.rejections());
+    assert_eq!(rejections("1+*"), Some(vec![Rejection::Syntax(2)]));
+    assert_eq!(rejections(&numbers[0]), Some(vec![Rejection::UnusedCards(hand[1..].to_vec())]));
+    assert_eq!(rejections(&format!("{}+13", numbers.join("+"))), Some(vec![Rejection::NotInHand(vec![13])]));
+
+    let error = AnswerError::Cards(CardsError { unused: vec![Card::new(CardType::Cup, 5)], extra: vec![13], ..CardsError::default() });
+    assert_eq!(error.to_string(), "unused cards [5], numbers not in the hand [13]");
+}
+
+#[test]
+fn biggest_deck_test() {
+    let cards = vec![Card::new(CardType::Cup, 1); decks::MAX_DECK];
+    let deck = Deck::new(&DeckKind::Custom { name: "ones".to_string(), cards });
+    assert_eq!(deck.as_ids().len(), decks::MAX_DECK);
+    assert_eq!(deck.as_ids().last(), Some(&254));
+}
 
+#[test]
+fn unchecked_test() {
+    // nothing makes 24 with ones, every hand is dealt unchecked
+    let cards = vec![Card::new(CardType::Cup, 1); 9];
+    let mut game = Game24::new(TableConfig { deck: DeckKind::Custom { name: "ones".to_string(), cards }, ..TableConfig::default() });
+    game.add_player();
+    assert_eq!(game.give_cards(), Deal::Unchecked);
+    assert_eq!(game.hand().len(), game.config.hand_size);
+    assert!(matches!(game.claim_no_solution(0), Claim::Accepted));
+    assert_eq!(game.give_cards(), Deal::Unchecked);
+    game.end_turn(TurnResult::Tie);
+    assert_eq!(game.give_cards(), Deal::Exhausted);
+    assert_eq!(game.unclaimed(), 5);
 }
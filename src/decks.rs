@@ -0,0 +1,164 @@
+use std::fs;
+
+use crate::common::{Card, CardType, DeckKind};
+
+// Ids of the cards in a stack are u8, from 0 to 254 for the biggest deck
+pub(crate) const MAX_DECK: usize = u8::MAX as usize;
+// The highest value or worth of a card, the king of a French deck
+pub(crate) const MAX_VALUE: u8 = 13;
+
+// The cards of the deck in the order they are put on the table before shuffling
+pub fn cards(kind: &DeckKind) -> Vec<Card> {
+    match kind {
+        DeckKind::Spanish => spanish(),
+        DeckKind::French => french(),
+        DeckKind::Custom { cards, .. } => cards.clone(),
+    }
+}
+
+// Numbered so each can have its own face, they count nothing by themselves
+fn jokers() -> Vec<Card> {
+    vec![Card::with_worth(CardType::Joker, 1, 0), Card::with_worth(CardType::Joker, 2, 0)]
+}
+
+fn suits(suits: &[CardType], high: u8) -> Vec<Card> {
+    suits.iter()
+        .flat_map(|suit| (1..=high).rev().map(move |value| Card::new(*suit, value)))
+        .collect()
+}
+
+// 48 cards, 1 to 12 of swords, clubs, golds and cups, and two jokers
+fn spanish() -> Vec<Card> {
+    let mut cards = jokers();
+    cards.append(&mut suits(&[CardType::Sword, CardType::Club, CardType::Gold, CardType::Cup], 12));
+    cards
+}
+
+// 52 cards, ace to king of spades, hearts, clovers and diamonds, and two jokers
+fn french() -> Vec<Card> {
+    let mut cards = jokers();
+    cards.append(&mut suits(&[CardType::Spade, CardType::Heart, CardType::Clover, CardType::Diamond], 13));
+    cards
+}
+
+pub fn suit(name: &str) -> Option<CardType> {
+    match name {
+        "gold" => Some(CardType::Gold),
+        "club" => Some(CardType::Club),
+        "sword" => Some(CardType::Sword),
+        "cup" => Some(CardType::Cup),
+        "heart" => Some(CardType::Heart),
+        "diamond" => Some(CardType::Diamond),
+        "clover" => Some(CardType::Clover),
+        "spade" => Some(CardType::Spade),
+        "joker" => Some(CardType::Joker),
+        _ => None,
+    }
+}
+
+// `7`, `1-10` or `11=10`, a card or a range of them, maybe worth another number
+fn values(word: &str) -> Option<Vec<(u8, u8)>> {
+    let (values, worth) = match word.split_once('=') {
+        Some((values, worth)) => (values, Some(worth.parse::<u8>().ok()?)),
+        None => (word, None),
+    };
+    let (low, high) = match values.split_once('-') {
+        Some((low, high)) => (low.parse::<u8>().ok()?, high.parse::<u8>().ok()?),
+        None => (values.parse::<u8>().ok()?, values.parse::<u8>().ok()?),
+    };
+    if low > high || high > MAX_VALUE || worth.is_some_and(|worth| worth > MAX_VALUE) {
+        return None;
+    }
+    Some((low..=high).map(|value| (value, worth.unwrap_or(value))).collect())
+}
+
+// A deck file has a line per suit with its values, `#` starts a comment:
+//
+//     heart 1-10 11=10 12=10 13=10
+//     joker 1-2=0
+pub fn parse(text: &str) -> Result<Vec<Card>, String> {
+    let mut cards = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let name = match words.next() {
+            Some(name) => name,
+            None => continue,
+        };
+        let _type = suit(name).ok_or(format!("line {}: unknown suit {}", number + 1, name))?;
+        for word in words {
+            let values = values(word).ok_or(format!("line {}: bad value {}", number + 1, word))?;
+            cards.extend(values.into_iter().map(|(value, worth)| Card::with_worth(_type, value, worth)));
+        }
+    }
+    check(&cards)?;
+    Ok(cards)
+}
+
+// What any deck has to be, read from a file here or sent by a client
+pub fn check(cards: &[Card]) -> Result<(), String> {
+    if cards.is_empty() {
+        return Err("the deck has no cards".to_string());
+    }
+    if cards.len() > MAX_DECK {
+        return Err(format!("the deck has more than {} cards", MAX_DECK));
+    }
+    if let Some(card) = cards.iter().find(|card| card.value > MAX_VALUE || card.worth > MAX_VALUE) {
+        return Err(format!("the {:?} {} is more than {}", card._type, card.value, MAX_VALUE));
+    }
+    Ok(())
+}
+
+// What the best card of the deck counts, the highest value of a wild joker
+pub fn highest(kind: &DeckKind) -> u8 {
+    cards(kind).iter()
+        .filter(|card| card._type != CardType::Joker)
+        .map(|card| card.worth)
+        .max()
+        .unwrap_or(0)
+}
+
+// `spanish`, `french` or the path of a deck file
+pub fn load(name: &str) -> Result<DeckKind, String> {
+    match name {
+        "spanish" => Ok(DeckKind::Spanish),
+        "french" => Ok(DeckKind::French),
+        path => {
+            let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            let cards = parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+            Ok(DeckKind::Custom { name: path.to_string(), cards })
+        }
+    }
+}
+
+#[test]
+fn decks_test() {
+    assert_eq!(spanish().len(), 50);
+    assert_eq!(french().len(), 54);
+    assert_eq!(french().iter().filter(|card| card._type == CardType::Heart).count(), 13);
+    assert_eq!(highest(&DeckKind::Spanish), 12);
+    assert_eq!(highest(&DeckKind::French), 13);
+    let cards = parse("heart 1-3 11=10\njoker 0\n").unwrap();
+    assert_eq!(highest(&DeckKind::Custom { name: "faces".to_string(), cards }), 10);
+}
+
+#[test]
+fn parse_test() {
+    let cards = parse("# faces are worth ten\nheart 1-3 11=10\n\njoker 0\n").unwrap();
+    assert_eq!(cards, vec![
+        Card::new(CardType::Heart, 1),
+        Card::new(CardType::Heart, 2),
+        Card::new(CardType::Heart, 3),
+        Card::with_worth(CardType::Heart, 11, 10),
+        Card::new(CardType::Joker, 0),
+    ]);
+    assert_eq!(parse("star 1-3"), Err("line 1: unknown suit star".to_string()));
+    assert_eq!(parse("cup 1\ncup 3-2"), Err("line 2: bad value 3-2".to_string()));
+    assert_eq!(parse("cup 14"), Err("line 1: bad value 14".to_string()));
+    assert_eq!(parse("cup 12=20"), Err("line 1: bad value 12=20".to_string()));
+    assert!(parse("# nothing").is_err());
+
+    let biggest = "cup 1-5\n".repeat(MAX_DECK / 5);
+    assert_eq!(parse(&biggest).map(|cards| cards.len()), Ok(MAX_DECK));
+    assert_eq!(parse(&format!("{}cup 1", biggest)), Err(format!("the deck has more than {} cards", MAX_DECK)));
+}
@@ -0,0 +1,191 @@
+use termion::event::Key;
+
+// What a key did to the line being edited
+#[derive(PartialEq, Eq, Debug)]
+pub enum Edit {
+    Editing,
+    Submit(String),
+}
+
+// The answer being typed: insert-mode editing, word jumps and the answers
+// already sent in this session, Up and Down go through them
+#[derive(Default)]
+pub struct LineEditor {
+    line: Vec<char>,
+    cursor: usize,                  // index in `line` where the next char goes
+    history: Vec<String>,
+    browsing: Option<usize>,        // the history entry shown, None for a new line
+    draft: Vec<char>,               // the new line, kept while browsing the history
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        LineEditor::default()
+    }
+
+    pub fn line(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn set_line(&mut self, line: Vec<char>) {
+        self.cursor = line.len();
+        self.line = line;
+    }
+
+    // Words are runs of digits or letters, operators and parens split them
+    fn is_word(c: char) -> bool {
+        c.is_alphanumeric() || c == '='
+    }
+
+    fn word_left(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && !LineEditor::is_word(self.line[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && LineEditor::is_word(self.line[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    fn word_right(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.line.len() && !LineEditor::is_word(self.line[i]) {
+            i += 1;
+        }
+        while i < self.line.len() && LineEditor::is_word(self.line[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    fn history_up(&mut self) {
+        let entry = match self.browsing {
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.line.clone();
+                self.history.len() - 1
+            }
+            Some(0) => return,
+            Some(entry) => entry - 1,
+        };
+        self.browsing = Some(entry);
+        self.set_line(self.history[entry].chars().collect());
+    }
+
+    fn history_down(&mut self) {
+        match self.browsing {
+            None => (),
+            Some(entry) if entry + 1 < self.history.len() => {
+                self.browsing = Some(entry + 1);
+                self.set_line(self.history[entry + 1].chars().collect());
+            }
+            Some(_) => {
+                self.browsing = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.set_line(draft);
+            }
+        }
+    }
+
+    // The line sent goes to the history, unless it repeats the last one
+    fn submit(&mut self) -> Edit {
+        let line = self.line();
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.browsing = None;
+        self.set_line(Vec::new());
+        Edit::Submit(line)
+    }
+
+    pub fn key(&mut self, key: Key) -> Edit {
+        match key {
+            Key::Char('\n') => return self.submit(),
+            Key::Char(c) if !c.is_control() => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            Key::CtrlLeft | Key::AltLeft | Key::Alt('b') => self.cursor = self.word_left(),
+            Key::CtrlRight | Key::AltRight | Key::Alt('f') => self.cursor = self.word_right(),
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = self.line.len(),
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            Key::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            Key::Ctrl('w') => {
+                let start = self.word_left();
+                self.line.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Key::Esc => {
+                self.browsing = None;
+                self.set_line(Vec::new());
+            }
+            Key::Up => self.history_up(),
+            Key::Down => self.history_down(),
+            _ => (),
+        }
+        Edit::Editing
+    }
+}
+
+#[cfg(test)]
+fn type_in(editor: &mut LineEditor, text: &str) {
+    for c in text.chars() {
+        editor.key(Key::Char(c));
+    }
+}
+
+#[test]
+fn editing_test() {
+    let mut editor = LineEditor::new();
+    editor.key(Key::Left);
+    assert_eq!(editor.cursor(), 0);
+    type_in(&mut editor, "12*3");
+    editor.key(Key::Home);
+    editor.key(Key::Delete);
+    type_in(&mut editor, "(1");
+    editor.key(Key::End);
+    type_in(&mut editor, ")");
+    assert_eq!(editor.line(), "(12*3)");
+    editor.key(Key::CtrlLeft);
+    assert_eq!(editor.cursor(), 4);
+    editor.key(Key::Backspace);
+    assert_eq!(editor.line(), "(123)");
+    editor.key(Key::CtrlLeft);
+    assert_eq!(editor.cursor(), 1);
+    editor.key(Key::Esc);
+    assert_eq!(editor.line(), "");
+    type_in(&mut editor, "8 * 3 + J=12");
+    editor.key(Key::Ctrl('w'));
+    assert_eq!(editor.line(), "8 * 3 + ");
+}
+
+#[test]
+fn history_test() {
+    let mut editor = LineEditor::new();
+    type_in(&mut editor, "1+2");
+    assert_eq!(editor.key(Key::Char('\n')), Edit::Submit("1+2".to_string()));
+    type_in(&mut editor, "3*4");
+    editor.key(Key::Char('\n'));
+    type_in(&mut editor, "5");
+    editor.key(Key::Up);
+    assert_eq!(editor.line(), "3*4");
+    editor.key(Key::Up);
+    editor.key(Key::Up);
+    assert_eq!(editor.line(), "1+2");
+    editor.key(Key::Down);
+    editor.key(Key::Down);
+    assert_eq!(editor.line(), "5");
+}
@@ -0,0 +1,183 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::answer::{Expr, Op};
+
+// Exact arithmetic for the answers: every value is kept as a reduced
+// fraction so `8/(3-8/3)` is exactly 24 and `48/3/2` never loses a remainder.
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Fraction {
+    num: i64,
+    den: i64, // always > 0 and coprime with num
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum EvalError {
+    Syntax(usize),      // 1-based column where the answer stops making sense
+    DivisionByZero,
+    Overflow,
+}
+
+impl Fraction {
+    pub fn new(num: i64, den: i64) -> Result<Self, EvalError> {
+        if den == 0 {
+            return Err(EvalError::DivisionByZero);
+        }
+        let g = gcd(num, den);
+        let (mut num, mut den) = (num / g, den / g);
+        if den < 0 {
+            num = num.checked_neg().ok_or(EvalError::Overflow)?;
+            den = den.checked_neg().ok_or(EvalError::Overflow)?;
+        }
+        Ok(Fraction { num, den })
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den == 1
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, EvalError> {
+        let num = mul(self.num, other.den)?
+            .checked_add(mul(other.num, self.den)?)
+            .ok_or(EvalError::Overflow)?;
+        Fraction::new(num, mul(self.den, other.den)?)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self, EvalError> {
+        let num = mul(self.num, other.den)?
+            .checked_sub(mul(other.num, self.den)?)
+            .ok_or(EvalError::Overflow)?;
+        Fraction::new(num, mul(self.den, other.den)?)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Result<Self, EvalError> {
+        Fraction::new(mul(self.num, other.num)?, mul(self.den, other.den)?)
+    }
+
+    pub fn checked_div(self, other: Self) -> Result<Self, EvalError> {
+        if other.num == 0 {
+            return Err(EvalError::DivisionByZero);
+        }
+        Fraction::new(mul(self.num, other.den)?, mul(self.den, other.num)?)
+    }
+
+    pub fn apply(self, op: Op, other: Self) -> Result<Self, EvalError> {
+        match op {
+            Op::Add => self.checked_add(other),
+            Op::Sub => self.checked_sub(other),
+            Op::Mul => self.checked_mul(other),
+            Op::Div => self.checked_div(other),
+        }
+    }
+}
+
+impl From<i64> for Fraction {
+    fn from(n: i64) -> Self {
+        Fraction { num: n, den: 1 }
+    }
+}
+
+impl PartialOrd for Fraction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Fraction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // denominators are positive, so cross multiplying keeps the order
+        (self.num as i128 * other.den as i128).cmp(&(other.num as i128 * self.den as i128))
+    }
+}
+
+impl fmt::Display for Fraction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::Syntax(column) => write!(f, "syntax error at column {}", column),
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::Overflow => write!(f, "number too big"),
+        }
+    }
+}
+
+fn mul(a: i64, b: i64) -> Result<i64, EvalError> {
+    a.checked_mul(b).ok_or(EvalError::Overflow)
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    // only den == 0 would give 0 here and that case is rejected before
+    a.max(1) as i64
+}
+
+pub fn eval(expr: &Expr) -> Result<Fraction, EvalError> {
+    match expr {
+        Expr::Number(n) | Expr::Joker(n) => Ok(Fraction::from(*n)),
+        Expr::BinOp(op, left, right) => eval(left)?.apply(*op, eval(right)?),
+        Expr::Parens(inner) => eval(inner),
+    }
+}
+
+// The value of an answer still being written: a trailing operator is left
+// out and the parens still open are closed, None while it makes no sense
+pub fn running_value(typed: &str) -> Option<Fraction> {
+    let mut text = typed.trim_end();
+    while let Some(rest) = text.strip_suffix(['+', '-', '*', '/', '(', '=', ' ']) {
+        text = rest;
+    }
+    let open = text.matches('(').count().checked_sub(text.matches(')').count())?;
+    let closed = format!("{}{}", text, ")".repeat(open));
+    eval(&crate::answer::parse(&closed).ok()?).ok()
+}
+
+#[cfg(test)]
+fn evaluate(answer: &str) -> Result<Fraction, EvalError> {
+    eval(&crate::answer::parse(answer).map_err(EvalError::Syntax)?)
+}
+
+#[test]
+fn fraction_test() {
+    assert_eq!(Fraction::new(6, -4), Ok(Fraction { num: -3, den: 2 }));
+    assert_eq!(Fraction::new(1, 0), Err(EvalError::DivisionByZero));
+    assert_eq!(Fraction::from(8).checked_div(Fraction::from(3)).unwrap().to_string(), "8/3");
+    assert_eq!(Fraction::from(i64::MAX).checked_add(Fraction::from(1)), Err(EvalError::Overflow));
+}
+
+#[test]
+fn evaluate_test() {
+    assert_eq!(evaluate("8/(3-8/3)"), Ok(Fraction::from(24)));
+    assert_eq!(evaluate(" 48 /  3/2"), Ok(Fraction::from(8)));
+    assert_eq!(evaluate("7/2"), Fraction::new(7, 2));
+    assert_eq!(evaluate("5/(3-3)"), Err(EvalError::DivisionByZero));
+    assert_eq!(evaluate("3 + 4 )"), Err(EvalError::Syntax(7)));
+    assert_eq!(evaluate("3 + "), Err(EvalError::Syntax(3)));
+    assert_eq!(evaluate("(1+2+3)*4"), Ok(Fraction::from(24)));
+}
+
+#[test]
+fn running_value_test() {
+    assert_eq!(running_value("(3+4"), Some(Fraction::from(7)));
+    assert_eq!(running_value("2*(3+4)*  "), Some(Fraction::from(14)));
+    assert_eq!(running_value("8/(3-8/3"), Some(Fraction::from(24)));
+    assert_eq!(running_value("8/3"), Fraction::new(8, 3).ok());
+    assert_eq!(running_value("(3+4))"), None);
+    assert_eq!(running_value(""), None);
+}
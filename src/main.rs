@@ -3,9 +3,16 @@ mod client;
 mod server;
 mod terminal;
 mod command;
+mod editor;
 mod core_cards;
-mod answer_analizer;
-//mod answer_numbers;
+mod decks;
+mod theme;
+mod answer;
+mod evaluator;
+mod solver;
+mod round;
+mod table;
+mod score;
 
 //#[macro_use]
 //extern crate serde_derive;
@@ -17,34 +24,93 @@ use message_io::network::{Transport, ToRemoteAddr};
 
 use std::net::{ToSocketAddrs};
 
+use theme::Theme;
+
+use common::{CARDCOUNT, DEFAULT_TABLE, DEFAULT_TARGET, JokerRule, MAX_CARDCOUNT, MIN_CARDCOUNT, TableConfig, UnsolvablePolicy};
+
 const HELP_MSG: &str = concat!(
-    "Usage: cardascii-24game table <port>\n",
-    "       cardascii-24game play (<ip-table>:<port> | url)"
+    "Usage: cardascii-24game table <port> [redeal | claim] [<seconds per round> [<target> [<cards per hand> [none | wild | <joker value> [spanish | french | <deck file>]]]]]\n",
+    "       cardascii-24game play (<ip-table>:<port> | url) [<table> [<nickname>]] [--theme <file or dir>]\n",
+    "       cardascii-24game tables (<ip-table>:<port> | url)"
 );
 
 pub fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
 
-    match args.get(1).unwrap_or(&"".into()).as_ref() {
-        "play" => match args.get(2) {
-            Some(remote_addr) => {
-                let remote_addr = remote_addr.to_remote_addr().unwrap();
-                client::run(Transport::Ws, remote_addr);
-                return;
+    // the only option, it can go anywhere after the command
+    let theme = match args.iter().position(|arg| arg == "--theme") {
+        Some(i) if i + 1 < args.len() => {
+            let path = args.remove(i + 1);
+            args.remove(i);
+            match Theme::load(&path) {
+                Ok(theme) => theme,
+                Err(e) => return println!("Can not load the theme, {}", e),
             }
-            None => (),
+        }
+        Some(_) => return println!("{HELP_MSG}"),
+        None => Theme::embedded(),
+    };
+
+    match args.get(1).unwrap_or(&"".into()).as_ref() {
+        "play" => if let Some(remote_addr) = args.get(2) {
+            let remote_addr = remote_addr.to_remote_addr().unwrap();
+            let table = args.get(3).cloned().unwrap_or_else(|| DEFAULT_TABLE.to_string());
+            let nickname = args.get(4).cloned()
+                .or_else(|| std::env::var("USER").ok())
+                .unwrap_or_else(|| "player".to_string());
+            client::run(Transport::Ws, remote_addr, table, nickname, theme);
+            return;
+        },
+        "tables" => if let Some(remote_addr) = args.get(2) {
+            client::list_tables(Transport::Ws, remote_addr.to_remote_addr().unwrap());
+            return;
         },
         "table" => {
-            match args.get(2).unwrap_or(&"".into()).parse() {
-                Ok(port) => {
-                    let addr = ("0.0.0.0", port).to_socket_addrs().unwrap().next().unwrap();
-                    server::run(Transport::Ws, addr);
-                    return;
-                }
-                Err(_) => () ,
+            let unsolvable = match args.get(3).map(String::as_str) {
+                None | Some("redeal") => Some(UnsolvablePolicy::Redeal),
+                Some("claim") => Some(UnsolvablePolicy::Claim),
+                Some(_) => None,
+            };
+            // without seconds, or with 0, the rounds last until somebody wins or everybody passes
+            let round_secs = match args.get(4).map(|secs| secs.parse::<u64>()) {
+                None | Some(Ok(0)) => Some(None),
+                Some(Ok(secs)) => Some(Some(secs)),
+                Some(Err(_)) => None,
+            };
+            let target = match args.get(5) {
+                None => Some(DEFAULT_TARGET),
+                Some(target) => target.parse::<i64>().ok(),
+            };
+            let hand_size = match args.get(6) {
+                None => Some(CARDCOUNT),
+                Some(size) => size.parse::<usize>().ok()
+                    .filter(|size| (MIN_CARDCOUNT..=MAX_CARDCOUNT).contains(size)),
             };
+            if let (Ok(port), Some(unsolvable), Some(round_secs), Some(target), Some(hand_size)) =
+                (args.get(2).unwrap_or(&"".into()).parse(), unsolvable, round_secs, target, hand_size) {
+                let deck = match decks::load(args.get(8).map(String::as_str).unwrap_or("spanish")) {
+                    Ok(deck) => deck,
+                    Err(e) => return println!("Can not load the deck, {}", e),
+                };
+                // a wild joker can be any card of the deck
+                let jokers = match args.get(7).map(String::as_str) {
+                    None | Some("none") => JokerRule::Excluded,
+                    Some("wild") => JokerRule::Wild(decks::highest(&deck)),
+                    Some(value) => match value.parse::<u8>() {
+                        Ok(value) => JokerRule::Fixed(value),
+                        Err(_) => return println!("{HELP_MSG}"),
+                    },
+                };
+                let addr = ("0.0.0.0", port).to_socket_addrs().unwrap().next().unwrap();
+                let config = TableConfig { unsolvable, round_secs, target, hand_size, jokers, deck };
+                if let Err(e) = config.check() {
+                    return println!("Can not open the table, {}", e);
+                }
+                server::run(Transport::Ws, addr, config);
+                return;
+            }
         }
         _ => (),
     }
-    return println!("{HELP_MSG}");
+    println!("{HELP_MSG}");
 }
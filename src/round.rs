@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::common::{Card, Countdown, HandCardData};
+
+// One hand on the table, from the deal to the first right answer or to
+// the tie when every player passes.
+// Answers are stamped when they arrive, so the winner is decided by
+// arrival order and not by the time the server spends checking them.
+pub struct Round {
+    number:     u8,
+    hand:       HandCardData,
+    started:    Instant,
+    limit:      Option<Duration>,
+    dealt_to:   HashSet<usize>,
+    passed:     HashSet<usize>,
+    winner:     Option<usize>,
+    closed_at:  Option<Instant>,
+}
+
+impl Round {
+    pub fn begin(number: u8, hand: HandCardData, limit: Option<Duration>) -> Self {
+        Round {
+            number,
+            hand,
+            started: Instant::now(),
+            limit,
+            dealt_to: HashSet::new(),
+            passed: HashSet::new(),
+            winner: None,
+            closed_at: None,
+        }
+    }
+
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    pub fn hand(&self) -> &[Card] {
+        &self.hand
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.closed_at.is_none()
+    }
+
+    // Time left at `now`, rounded up so the last second still shows as 1
+    pub fn countdown(&self, now: Instant) -> Option<Countdown> {
+        self.limit.map(|limit| {
+            Countdown::new(limit.saturating_sub(now.saturating_duration_since(self.started)), limit.as_secs())
+        })
+    }
+
+    fn past_limit(&self, at: Instant) -> bool {
+        match self.limit {
+            Some(limit) => at.saturating_duration_since(self.started) >= limit,
+            None => false,
+        }
+    }
+
+    // The deadline is the server's, whatever the clients show
+    pub fn expired(&self, now: Instant) -> bool {
+        self.is_open() && self.past_limit(now)
+    }
+
+    // Remembers who has seen the hand, false if that seat already had it
+    pub fn deal_to(&mut self, seat: usize) -> bool {
+        self.dealt_to.insert(seat)
+    }
+
+    // An answer arrived at `at` is still in time if the round did not end before
+    // it, the deadline counts even if the tick that closes the round is still to come
+    pub fn in_time(&self, at: Instant) -> bool {
+        let closed = match self.closed_at {
+            Some(closed_at) => at >= closed_at,
+            None => false,
+        };
+        !closed && !self.past_limit(at)
+    }
+
+    // The first right answer closes the round, gives how long it took
+    pub fn close(&mut self, seat: usize, at: Instant) -> Option<Duration> {
+        if !self.is_open() {
+            return None;
+        }
+        self.winner = Some(seat);
+        self.closed_at = Some(at);
+        Some(at.saturating_duration_since(self.started))
+    }
+
+    // A player gives up this hand, false if it had already passed
+    pub fn pass(&mut self, seat: usize) -> bool {
+        self.passed.insert(seat)
+    }
+
+    pub fn passes(&self) -> usize {
+        self.passed.len()
+    }
+
+    // True when every one of the seats has passed
+    pub fn all_passed<'a>(&self, mut seats: impl Iterator<Item = &'a usize>) -> bool {
+        seats.all(|seat| self.passed.contains(seat))
+    }
+
+    // Nobody found the answer, the round closes without winner
+    pub fn tie(&mut self, at: Instant) {
+        if self.is_open() {
+            self.closed_at = Some(at);
+        }
+    }
+}
+
+#[test]
+fn round_test() {
+    use crate::common::{CardType, CARDCOUNT};
+    let mut round = Round::begin(1, vec![Card::new(CardType::Cup, 6); CARDCOUNT], None);
+    assert!(round.deal_to(0));
+    assert!(!round.deal_to(0));
+
+    let first = Instant::now();
+    let second = first + Duration::from_millis(10);
+    assert!(round.close(1, first).is_some());
+    assert!(!round.is_open());
+    assert!(round.close(0, second).is_none());
+    assert!(!round.in_time(second));
+}
+
+#[test]
+fn pass_test() {
+    use crate::common::{CardType, CARDCOUNT};
+    let mut round = Round::begin(1, vec![Card::new(CardType::Cup, 6); CARDCOUNT], None);
+    let seats = [0, 2];
+    assert!(round.pass(0));
+    assert!(!round.pass(0));
+    assert!(!round.all_passed(seats.iter()));
+    assert!(round.pass(2));
+    assert!(round.all_passed(seats.iter()));
+    assert_eq!(round.passes(), 2);
+
+    round.tie(Instant::now());
+    assert!(!round.is_open());
+    assert!(round.close(0, Instant::now()).is_none());
+}
+
+#[test]
+fn countdown_test() {
+    use crate::common::{CardType, CARDCOUNT};
+    let round = Round::begin(1, vec![Card::new(CardType::Cup, 6); CARDCOUNT], Some(Duration::from_secs(30)));
+    let start = round.started;
+    assert_eq!(round.countdown(start), Some(Countdown { left: 30, total: 30 }));
+    assert_eq!(round.countdown(start + Duration::from_millis(10500)), Some(Countdown { left: 20, total: 30 }));
+    assert!(!round.expired(start + Duration::from_secs(29)));
+    assert!(round.expired(start + Duration::from_secs(30)));
+    // still open, but an answer after the deadline is late
+    assert!(round.in_time(start + Duration::from_secs(29)));
+    assert!(!round.in_time(start + Duration::from_secs(30)));
+    assert_eq!(round.countdown(start + Duration::from_secs(31)), Some(Countdown { left: 0, total: 30 }));
+}
@@ -0,0 +1,33 @@
+use crate::common::ScoreEntry;
+
+// Orders the players by the cards they won, ties share the rank and the
+// next rank skips as many places as players tied (1, 1, 3)
+pub fn ranking(nicknames: &[String], cards: &[usize]) -> Vec<ScoreEntry> {
+    let mut entries: Vec<ScoreEntry> = nicknames.iter().zip(cards.iter())
+        .map(|(nickname, cards)| ScoreEntry { rank: 0, nickname: nickname.clone(), cards: *cards })
+        .collect();
+    entries.sort_by(|a, b| b.cards.cmp(&a.cards).then_with(|| a.nickname.cmp(&b.nickname)));
+
+    for i in 0..entries.len() {
+        entries[i].rank = if i > 0 && entries[i].cards == entries[i - 1].cards {
+            entries[i - 1].rank
+        } else {
+            i + 1
+        };
+    }
+    entries
+}
+
+#[test]
+fn ranking_test() {
+    let nicknames: Vec<String> = ["ana", "bob", "cid", "dan"].iter().map(|n| n.to_string()).collect();
+    let ranks: Vec<(usize, String, usize)> = ranking(&nicknames, &[4, 12, 4, 0]).into_iter()
+        .map(|entry| (entry.rank, entry.nickname, entry.cards))
+        .collect();
+    assert_eq!(ranks, vec![
+        (1, "bob".to_string(), 12),
+        (2, "ana".to_string(), 4),
+        (2, "cid".to_string(), 4),
+        (4, "dan".to_string(), 0),
+    ]);
+}
@@ -1,108 +1,303 @@
-use std::any::Any;
 use super::common::{FromServerMessage, FromClientMessage};
 
 use message_io::network::{NetEvent, Transport, Endpoint};
-use message_io::node::{self};
+use message_io::node::{self, NodeEvent, NodeHandler};
 
-use std::collections::{HashMap, HashSet};
-use std::fmt::Debug;
-use std::iter::FromIterator;
+use std::collections::HashMap;
 use std::net::{SocketAddr};
-use nom::ToUsize;
-use rcalc::{ASTNode, Interpreter, Lexer, Parser, Token};
-use crate::answer_analizer;
-use crate::common::{Card, CARDCOUNT, CardType, HandCardData, TurnEndType};
-use crate::core_cards::{Game24, TurnResult};
+use std::time::{Duration, Instant};
+use crate::common::{DEFAULT_TABLE, Handshake, MAX_ANSWER, MAX_NICKNAME, MAX_TABLE_NAME, Rejection, TableConfig};
+use crate::table::{Outgoing, Table};
+
+// Bigger than any message a client needs, a table with a custom deck included
+const MAX_PAYLOAD: usize = 64 * 1024;
+
+enum Signal {
+    Tick, // Every second, to keep the round deadlines
+}
 
 struct ClientInfo {
     id: usize,
+    shaken: bool,               // its handshake came and speaks our protocol
+    nickname: Option<String>,   // known after the hello
+    table: Option<String>,
+}
+
+// The printable part of a name a client chose, at most `max` characters: it
+// goes to the server logs and to the screens of the other players
+fn clean_name(wanted: &str, max: usize) -> String {
+    let name: String = wanted.chars().filter(|c| !c.is_control()).take(max).collect();
+    name.trim().to_string()
+}
+
+// Keeps the printable part of the nickname and makes it unique among the ones
+// taken by the other clients, cutting it so the `#id` that tells it apart still fits
+fn assign_nickname(taken: &[&str], id: usize, wanted: &str) -> String {
+    let mut nickname = clean_name(wanted, MAX_NICKNAME);
+    if nickname.is_empty() {
+        nickname = "player".to_string();
+    }
+    // somebody may have typed the same name with the suffix already,
+    // the next numbers are tried until one is free
+    let wanted = nickname.clone();
+    let mut number = id;
+    while taken.contains(&nickname.as_str()) {
+        let suffix = format!("#{}", number);
+        let base: String = wanted.chars().take(MAX_NICKNAME.saturating_sub(suffix.len())).collect();
+        nickname = format!("{}{}", base.trim_end(), suffix);
+        number += 1;
+    }
+    nickname
+}
+
+fn send(handler: &NodeHandler<Signal>, endpoint: Endpoint, message: &FromServerMessage) {
+    let output_data = bincode::serialize(message).unwrap();
+    handler.network().send(endpoint, &output_data);
+}
+
+// The tables speak of client ids, the connections are looked up here
+fn send_all(handler: &NodeHandler<Signal>, endpoints: &HashMap<usize, Endpoint>, outgoing: Outgoing) {
+    for (client, message) in outgoing.iter() {
+        if let Some(endpoint) = endpoints.get(client) {
+            send(handler, *endpoint, message);
+        }
+    }
+}
+
+// Takes the client out of its table, the tables nobody plays at are closed
+// except the default one
+fn leave_table(handler: &NodeHandler<Signal>, tables: &mut HashMap<String, Table>,
+               endpoints: &HashMap<usize, Endpoint>, client: &mut ClientInfo) {
+    if let Some(name) = client.table.take() {
+        if let Some(table) = tables.get_mut(&name) {
+            send_all(handler, endpoints, table.leave(client.id, Instant::now()));
+            if table.is_empty() && name != DEFAULT_TABLE {
+                tables.remove(&name);
+                println!("Table {} closed", name);
+            }
+        }
+    }
+}
+
+// Closes the connection of a client we can not talk with
+fn drop_client(handler: &NodeHandler<Signal>, tables: &mut HashMap<String, Table>,
+               clients: &mut HashMap<Endpoint, ClientInfo>, endpoints: &mut HashMap<usize, Endpoint>,
+               endpoint: Endpoint, reason: &str) {
+    if let Some(mut client) = clients.remove(&endpoint) {
+        leave_table(handler, tables, endpoints, &mut client);
+        endpoints.remove(&client.id);
+        // only connection oriented clients are kept, the resource is their own
+        handler.network().remove(endpoint.resource_id());
+    }
+    println!("Client ({}) dropped, {} (total clients: {})", endpoint.addr(), reason, clients.len());
+}
+
+fn say_hello_first() -> FromServerMessage {
+    FromServerMessage::SendMsg("say hello with your nickname before sitting at a table".to_string())
 }
 
-pub fn run(transport: Transport, addr: SocketAddr) {
-    let (handler, listener) = node::split::<()>();
+pub fn run(transport: Transport, addr: SocketAddr, config: TableConfig) {
+    let (handler, listener) = node::split::<Signal>();
 
     let mut clients= HashMap::<Endpoint, ClientInfo>::new();
+    let mut endpoints = HashMap::<usize, Endpoint>::new();   // the connection of each client id
     let mut id = 0;
 
-    let mut game = Game24::new();
-
-    let mut cards: HandCardData =
-        [ Card{ _type : CardType::Joker, value : 0} ; CARDCOUNT];
+    let mut tables = HashMap::<String, Table>::new();
+    tables.insert(DEFAULT_TABLE.to_string(), Table::new(DEFAULT_TABLE.to_string(), config));
 
     match handler.network().listen(transport, addr) {
-        Ok((_id, real_addr)) => println!("Server running at {} by {}", real_addr, transport),
+        Ok((_id, real_addr)) => {
+            println!("Server running at {} by {}", real_addr, transport);
+            handler.signals().send_with_timer(Signal::Tick, Duration::from_secs(1));
+        }
         Err(_) => return println!("Can not listening at {} by {}", addr, transport),
     }
 
-    listener.for_each(move |event| match event.network() {
+    listener.for_each(move |event| match event {
+        NodeEvent::Signal(Signal::Tick) => {
+            let now = Instant::now();
+            for table in tables.values_mut() {
+                send_all(&handler, &endpoints, table.tick(now));
+            }
+            handler.signals().send_with_timer(Signal::Tick, Duration::from_secs(1));
+        }
+        NodeEvent::Network(net_event) => match net_event {
         NetEvent::Connected(_, _) => (), // Only generated at connect() calls.
         NetEvent::Accepted(endpoint, _listener_id) => {
             // Only connection oriented protocols will generate this event
 
-            clients.insert(endpoint, ClientInfo { id }); id += 1;
+            clients.insert(endpoint, ClientInfo { id, shaken: false, nickname: None, table: None });
+            endpoints.insert(id, endpoint);
+            id += 1;
 
             println!("Client ({}) connected (total clients: {})", endpoint.addr(), clients.len());
         }
         NetEvent::Message(endpoint, input_data) => {
-            let message: FromClientMessage = bincode::deserialize(&input_data).unwrap();
+            // answers are stamped on arrival, before any checking
+            let at = Instant::now();
+            if input_data.len() > MAX_PAYLOAD {
+                let message = format!("message of {} bytes refused, at most {}", input_data.len(), MAX_PAYLOAD);
+                return send(&handler, endpoint, &FromServerMessage::SendMsg(message));
+            }
+            // the first message is the handshake, ours goes back either way so
+            // a client of another version can tell its player why it is refused
+            if let Some(client) = clients.get_mut(&endpoint).filter(|client| !client.shaken) {
+                handler.network().send(endpoint, &bincode::serialize(&Handshake::ours()).unwrap());
+                return match Handshake::check(input_data) {
+                    Ok(()) => client.shaken = true,
+                    Err(e) => drop_client(&handler, &mut tables, &mut clients, &mut endpoints, endpoint, &e),
+                };
+            }
+            let message: FromClientMessage = match bincode::deserialize(input_data) {
+                Ok(message) => message,
+                Err(e) => return drop_client(&handler, &mut tables, &mut clients, &mut endpoints, endpoint, &e.to_string()),
+            };
+            if let FromClientMessage::Hello { nickname } = &message {
+                if let Some(client_id) = clients.get(&endpoint).map(|client| client.id) {
+                    let taken: Vec<&str> = clients.values()
+                        .filter(|client| client.id != client_id)
+                        .filter_map(|client| client.nickname.as_deref())
+                        .collect();
+                    let nickname = assign_nickname(&taken, client_id, nickname);
+                    println!("Client ({}) is {}", endpoint.addr(), nickname);
+                    let client = clients.get_mut(&endpoint).unwrap();
+                    client.nickname = Some(nickname.clone());
+                    // the table it sits at shows the new name from now on
+                    if let Some(table) = client.table.as_ref().and_then(|name| tables.get_mut(name)) {
+                        send_all(&handler, &endpoints, table.rename(client_id, &nickname));
+                    }
+                    return send(&handler, endpoint, &FromServerMessage::Welcome { id: client_id, nickname });
+                }
+            }
+            let client = match clients.get_mut(&endpoint) {
+                Some(client) => client,
+                None => {
+                    // For non-connection oriented protocols
+                    println!("Ping from {}", endpoint.addr());
+                    return send(&handler, endpoint, &FromServerMessage::UnknownPong);
+                }
+            };
+            let table = client.table.as_ref().and_then(|name| tables.get_mut(name));
+
             match message {
                 FromClientMessage::Ping => {
-                    let message = match clients.get_mut(&endpoint) {
-                        Some(client) => {
-                            // For connection oriented protocols
-                            println!("Ping from {}, {} times", endpoint.addr(), client.id);
-                            FromServerMessage::Pong(client.id)
-                        }
-                        None => {
-                            // For non-connection oriented protocols
-                            println!("Ping from {}", endpoint.addr());
-                            FromServerMessage::UnknownPong
-                        }
-                    };
-                    let output_data = bincode::serialize(&message).unwrap();
-                    handler.network().send(endpoint, &output_data);
+                    // For connection oriented protocols
+                    println!("Ping from {}, {} times", endpoint.addr(), client.id);
+                    send(&handler, endpoint, &FromServerMessage::Pong(client.id));
                 },
-                FromClientMessage::NewTurn => {
-                    if game.give_cards() {
-
-                        for i in 0..CARDCOUNT {
-                            if let Some(card) = game.get_gived_card(i) {
-                                cards[i] = card.clone();
-                                println!("{:?}", card);
-                            }
-                        }
-                        let message = FromServerMessage::TurnBegin( cards );
-                        let output_data = bincode::serialize(&message).unwrap();
-                        handler.network().send(endpoint, &output_data);
+                FromClientMessage::NewTurn => if let Some(table) = table {
+                    send_all(&handler, &endpoints, table.new_turn(client.id));
+                }
+                FromClientMessage::TurnAnswer(answer) => if let Some(table) = table {
+                    // the answer is taken from the connection, never from what the client claims
+                    if answer.chars().count() > MAX_ANSWER {
+                        let reasons = vec![Rejection::TooLong(MAX_ANSWER)];
+                        return send(&handler, endpoint, &FromServerMessage::Rejected(reasons));
                     }
+                    println!("{} say >> {}", client.nickname.as_deref().unwrap_or("?"), answer);
+                    send_all(&handler, &endpoints, table.answer(client.id, &answer, at));
                 }
-                FromClientMessage::TurnAnswer(user_id, entry) => {
-                    let answer = String::from_iter(entry);
-                    println!("user:say >> {}", answer);
-
-                    let mut message = FromServerMessage::TurnContinue;
-
-
-
-                    // FIXME llevar esto tambien a game::make_answer
-                    let mut program = Interpreter::from(answer.as_str());
-
-                    if let Ok( result  ) = program.interpret() { //answer_analizer::analize(&answer) {
-                        println!("@ {}", result);
-                        if result == 24.0 {
-                            message = match game.make_answer(user_id, answer) {
-                                Ok(()) =>       FromServerMessage::TurnEnd(TurnEndType::YouWin),
-                                Err(msgErr) =>  FromServerMessage::SendMsg(msgErr)
-                            }
-                        }
+                FromClientMessage::NewGame => if let Some(table) = table {
+                    send_all(&handler, &endpoints, table.new_game(client.id));
+                }
+                FromClientMessage::NoSolution => if let Some(table) = table {
+                    send_all(&handler, &endpoints, table.claim_no_solution(client.id, at));
+                }
+                FromClientMessage::Pass => if let Some(table) = table {
+                    send_all(&handler, &endpoints, table.pass(client.id, at));
+                }
+                FromClientMessage::ListTables => {
+                    let mut list: Vec<_> = tables.values().map(Table::info).collect();
+                    list.sort_by(|a, b| a.name.cmp(&b.name));
+                    send(&handler, endpoint, &FromServerMessage::TableList(list));
+                }
+                FromClientMessage::CreateTable(name, config) => {
+                    let nickname = match client.nickname.clone() {
+                        Some(nickname) => nickname,
+                        None => return send(&handler, endpoint, &say_hello_first()),
+                    };
+                    let name = clean_name(&name, MAX_TABLE_NAME);
+                    if name.is_empty() {
+                        let message = "a table needs a name".to_string();
+                        return send(&handler, endpoint, &FromServerMessage::SendMsg(message));
                     }
-                    println!("{message}");
-                    let output_data = bincode::serialize(&message).unwrap();
-                    handler.network().send(endpoint, &output_data);
+                    if tables.contains_key(&name) {
+                        let message = format!("table {} already exists", name);
+                        return send(&handler, endpoint, &FromServerMessage::SendMsg(message));
+                    }
+                    if let Err(e) = config.check() {
+                        let message = format!("can not create table {}, {}", name, e);
+                        return send(&handler, endpoint, &FromServerMessage::SendMsg(message));
+                    }
+                    println!("Table {} created by {}", name, endpoint.addr());
+                    leave_table(&handler, &mut tables, &endpoints, client);
+                    let mut table = Table::new(name.clone(), config);
+                    let outgoing = table.join(client.id, &nickname);
+                    tables.insert(name.clone(), table);
+                    client.table = Some(name.clone());
+                    send(&handler, endpoint, &FromServerMessage::TableJoined(name));
+                    send_all(&handler, &endpoints, outgoing);
                 }
-
+                FromClientMessage::JoinTable(name) => {
+                    let nickname = match client.nickname.clone() {
+                        Some(nickname) => nickname,
+                        None => return send(&handler, endpoint, &say_hello_first()),
+                    };
+                    if !tables.contains_key(&name) {
+                        return send(&handler, endpoint, &FromServerMessage::NoSuchTable(name));
+                    }
+                    // already sitting there, the seat stays as it is
+                    if client.table.as_ref() == Some(&name) {
+                        return send(&handler, endpoint, &FromServerMessage::TableJoined(name));
+                    }
+                    leave_table(&handler, &mut tables, &endpoints, client);
+                    let outgoing = match tables.get_mut(&name) {
+                        Some(table) => table.join(client.id, &nickname),
+                        None => vec![],
+                    };
+                    client.table = Some(name.clone());
+                    send(&handler, endpoint, &FromServerMessage::TableJoined(name));
+                    send_all(&handler, &endpoints, outgoing);
+                }
+                FromClientMessage::Hello { .. } => unreachable!(), // answered above
+                FromClientMessage::LeaveTable => {
+                    leave_table(&handler, &mut tables, &endpoints, client);
+                    send(&handler, endpoint, &FromServerMessage::TableLeft);
+                }
+            }
+        },
+        NetEvent::Disconnected(endpoint) => {
+            if let Some(mut client) = clients.remove(&endpoint) {
+                leave_table(&handler, &mut tables, &endpoints, &mut client);
+                endpoints.remove(&client.id);
             }
+            println!("Client ({}) disconnected (total clients: {})", endpoint.addr(), clients.len());
+        }
         },
-        NetEvent::Disconnected(endpoint) => {}
     });
 }
+
+#[test]
+fn clean_name_test() {
+    assert_eq!(clean_name("  my table ", MAX_TABLE_NAME), "my table");
+    assert_eq!(clean_name("\x1b[31mred\x1b[0m", MAX_TABLE_NAME), "[31mred[0m");
+    assert_eq!(clean_name("\n\t", MAX_TABLE_NAME), "");
+    assert_eq!(clean_name("a table with a long name", MAX_TABLE_NAME), "a table with a l");
+}
+
+#[test]
+fn assign_nickname_test() {
+    let taken = ["ana", "a_long_nickname!"];
+    assert_eq!(assign_nickname(&taken, 3, "bob"), "bob");
+    assert_eq!(assign_nickname(&taken, 3, " \x07 "), "player");
+    assert_eq!(assign_nickname(&taken, 3, "ana"), "ana#3");
+    // the suffix does not push the name over the limit
+    let nickname = assign_nickname(&taken, 123, "a_long_nickname!");
+    assert_eq!(nickname, "a_long_nickn#123");
+    assert_eq!(nickname.chars().count(), MAX_NICKNAME);
+    // the suffixed name was taken too
+    assert_eq!(assign_nickname(&["ana", "ana#3", "ana#4"], 3, "ana"), "ana#5");
+    // saying hello again keeps the own name, it is not among the taken ones
+    assert_eq!(assign_nickname(&["a_long_nickname!"], 1, "ana"), "ana");
+}
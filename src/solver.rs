@@ -0,0 +1,329 @@
+use std::collections::HashSet;
+use std::time::Instant;
+
+use crate::answer::{Expr, Op};
+use crate::common::{Card, JokerRule};
+use crate::evaluator::Fraction;
+
+// Expressions are searched in a normalized shape so that `a+b` and `b+a`,
+// or `a-(b-c)` and `a-b+c`, are found only once: sums keep their added and
+// subtracted terms, products their multiplied and divided factors, all sorted.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Debug)]
+enum Canon {
+    Number(i64),
+    Joker(i64),
+    Sum(Vec<Canon>, Vec<Canon>),
+    Product(Vec<Canon>, Vec<Canon>),
+}
+
+impl Canon {
+    fn sum_parts(self) -> (Vec<Canon>, Vec<Canon>) {
+        match self {
+            Canon::Sum(pos, neg) => (pos, neg),
+            other => (vec![other], vec![]),
+        }
+    }
+
+    fn product_parts(self) -> (Vec<Canon>, Vec<Canon>) {
+        match self {
+            Canon::Product(num, den) => (num, den),
+            other => (vec![other], vec![]),
+        }
+    }
+
+    fn combine(op: Op, left: &Canon, right: &Canon) -> Canon {
+        let (left, right) = (left.clone(), right.clone());
+        let (mut keep, mut invert) = match op {
+            Op::Add | Op::Sub => left.sum_parts(),
+            Op::Mul | Op::Div => left.product_parts(),
+        };
+        let (mut right_keep, mut right_invert) = match op {
+            Op::Add | Op::Sub => right.sum_parts(),
+            Op::Mul | Op::Div => right.product_parts(),
+        };
+        match op {
+            Op::Add | Op::Mul => {
+                keep.append(&mut right_keep);
+                invert.append(&mut right_invert);
+            }
+            Op::Sub | Op::Div => {
+                keep.append(&mut right_invert);
+                invert.append(&mut right_keep);
+            }
+        }
+        keep.sort();
+        invert.sort();
+        match op {
+            Op::Add | Op::Sub => Canon::Sum(keep, invert),
+            Op::Mul | Op::Div => Canon::Product(keep, invert),
+        }
+    }
+
+    fn to_expr(&self) -> Expr {
+        match self {
+            Canon::Number(n) => Expr::Number(*n),
+            Canon::Joker(n) => Expr::Joker(*n),
+            Canon::Sum(pos, neg) => chain(pos, neg, Op::Add, Op::Sub),
+            Canon::Product(num, den) => chain(num, den, Op::Mul, Op::Div),
+        }
+    }
+}
+
+// Being flattened, a sum only holds numbers and products and a product only
+// numbers and sums, so the sums inside a product are the only parens needed
+fn chain(keep: &[Canon], invert: &[Canon], op: Op, inverse: Op) -> Expr {
+    let operand = |canon: &Canon| match canon {
+        Canon::Sum(..) if op == Op::Mul => Expr::Parens(Box::new(canon.to_expr())),
+        _ => canon.to_expr(),
+    };
+    let mut operands = keep.iter().map(|canon| (op, operand(canon)))
+        .chain(invert.iter().map(|canon| (inverse, operand(canon))));
+
+    let (_, first) = operands.next().expect("normalized expressions are never empty");
+    operands.fold(first, |acc, (op, expr)| Expr::BinOp(op, Box::new(acc), Box::new(expr)))
+}
+
+const OPS: [Op; 4] = [Op::Add, Op::Sub, Op::Mul, Op::Div];
+
+// Every value that can be made with two partial results, both orders
+// are tried for the operations that are not commutative
+fn combinations(a: &(Fraction, Canon), b: &(Fraction, Canon)) -> Vec<(Fraction, Canon)> {
+    let mut results = Vec::new();
+    for op in OPS.iter() {
+        let mut orders = vec![(a, b)];
+        if *op == Op::Sub || *op == Op::Div {
+            orders.push((b, a));
+        }
+        for (left, right) in orders {
+            if let Ok(value) = left.0.apply(*op, right.0) {
+                results.push((value, Canon::combine(*op, &left.1, &right.1)));
+            }
+        }
+    }
+    results
+}
+
+fn search(items: Vec<(Fraction, Canon)>, target: Fraction, limit: usize,
+          solutions: &mut HashSet<Canon>, seen: &mut HashSet<Vec<Canon>>, budget: &mut Budget) {
+    if solutions.len() >= limit || budget.spent() {
+        return;
+    }
+    if items.len() == 1 {
+        if items[0].0 == target {
+            solutions.insert(items[0].1.clone());
+        }
+        return;
+    }
+
+    let mut state: Vec<Canon> = items.iter().map(|(_, canon)| canon.clone()).collect();
+    state.sort();
+    if !seen.insert(state) {
+        return;
+    }
+
+    for i in 0..items.len() {
+        for j in (i + 1)..items.len() {
+            let rest: Vec<(Fraction, Canon)> = items.iter().enumerate()
+                .filter(|(k, _)| *k != i && *k != j)
+                .map(|(_, item)| item.clone())
+                .collect();
+            for combined in combinations(&items[i], &items[j]) {
+                let mut next = rest.clone();
+                next.push(combined);
+                search(next, target, limit, solutions, seen, budget);
+            }
+        }
+    }
+}
+
+// The hand as the numbers to combine, once for every value the wild jokers can take
+fn leaves(hand: &[Card], jokers: JokerRule) -> Vec<Vec<(Fraction, Canon)>> {
+    let mut all = vec![vec![]];
+    for card in hand {
+        all = match jokers.face_value(card) {
+            Some(value) => all.into_iter().map(|mut items| {
+                items.push((Fraction::from(value), Canon::Number(value)));
+                items
+            }).collect(),
+            None => all.iter().flat_map(|items| jokers.wild_values().map(move |value| {
+                let mut items = items.clone();
+                items.push((Fraction::from(value), Canon::Joker(value)));
+                items
+            })).collect(),
+        };
+    }
+    all
+}
+
+// The distinct ways of making the target with every card of the hand, the one
+// way to list them: all of them with no limit and no deadline, but the big
+// hands have thousands and take seconds, so callers cap how many and how long.
+// The ones found when the deadline comes, none if the search for the first
+// one runs out of time
+pub fn solve(hand: &[Card], target: i64, jokers: JokerRule, limit: usize, deadline: Option<Instant>) -> Vec<Expr> {
+    if has_solution_before(hand, target, jokers, deadline) != Some(true) {
+        return vec![];
+    }
+    let mut budget = Budget { deadline, steps: 0, spent: false };
+    let mut solutions = HashSet::new();
+    let mut seen = HashSet::new();
+    for items in leaves(hand, jokers) {
+        search(items, Fraction::from(target), limit, &mut solutions, &mut seen, &mut budget);
+    }
+
+    let mut solutions: Vec<Canon> = solutions.into_iter().collect();
+    solutions.sort();
+    solutions.iter().map(Canon::to_expr).collect()
+}
+
+// Stops a search that takes too long, the clock is read every few thousand states
+struct Budget {
+    deadline: Option<Instant>,
+    steps: u32,
+    spent: bool,
+}
+
+impl Budget {
+    fn spent(&mut self) -> bool {
+        self.steps = self.steps.wrapping_add(1);
+        if self.steps & 0xFFF == 0 {
+            self.spent |= self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        }
+        self.spent
+    }
+}
+
+fn reachable(values: Vec<Fraction>, target: Fraction, dead_ends: &mut HashSet<Vec<Fraction>>, budget: &mut Budget) -> bool {
+    if values.len() == 1 {
+        return values[0] == target;
+    }
+    if budget.spent() {
+        return false;
+    }
+    let mut state = values.clone();
+    state.sort();
+    if dead_ends.contains(&state) {
+        return false;
+    }
+
+    for i in 0..values.len() {
+        for j in (i + 1)..values.len() {
+            let rest: Vec<Fraction> = values.iter().enumerate()
+                .filter(|(k, _)| *k != i && *k != j)
+                .map(|(_, value)| *value)
+                .collect();
+            for op in OPS.iter() {
+                for (left, right) in [(values[i], values[j]), (values[j], values[i])] {
+                    if let Ok(value) = left.apply(*op, right) {
+                        let mut next = rest.clone();
+                        next.push(value);
+                        if reachable(next, target, dead_ends, budget) {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    // a search cut short proves nothing
+    if !budget.spent {
+        dead_ends.insert(state);
+    }
+    false
+}
+
+// Cheaper than `solve` when only the existence of a solution matters
+pub fn has_solution(hand: &[Card], target: i64, jokers: JokerRule) -> bool {
+    has_solution_before(hand, target, jokers, None) == Some(true)
+}
+
+// None if the deadline comes before the answer, big hands without a
+// solution can take the best part of a second to rule out
+pub fn has_solution_before(hand: &[Card], target: i64, jokers: JokerRule, deadline: Option<Instant>) -> Option<bool> {
+    let mut budget = Budget { deadline, steps: 0, spent: false };
+    // the dead ends only depend on the values, so every joker value shares them
+    let mut dead_ends = HashSet::new();
+    let found = leaves(hand, jokers).into_iter().any(|items| {
+        let values = items.into_iter().map(|(value, _)| value).collect();
+        reachable(values, Fraction::from(target), &mut dead_ends, &mut budget)
+    });
+    match (found, budget.spent) {
+        (true, _) => Some(true),
+        (false, true) => None,
+        (false, false) => Some(false),
+    }
+}
+
+#[cfg(test)]
+fn hand(values: [u8; 4]) -> Vec<Card> {
+    use crate::common::CardType;
+    values.iter().map(|value| Card::new(CardType::Cup, *value)).collect()
+}
+
+#[test]
+fn solve_test() {
+    let solutions: Vec<String> = solve(&hand([3, 3, 8, 8]), 24, JokerRule::Excluded, usize::MAX, None).iter().map(Expr::to_string).collect();
+    assert_eq!(solutions, vec!["8/(3-8/3)"]);
+
+    let solutions = solve(&hand([1, 2, 3, 4]), 24, JokerRule::Excluded, usize::MAX, None);
+    assert!(solutions.iter().any(|e| e.to_string() == "1*2*3*4"));
+    for solution in solutions.iter() {
+        assert_eq!(crate::evaluator::eval(solution), Ok(Fraction::from(24)));
+    }
+    let mut distinct: Vec<String> = solutions.iter().map(Expr::to_string).collect();
+    distinct.dedup();
+    assert_eq!(distinct.len(), solutions.len());
+}
+
+#[test]
+fn has_solution_test() {
+    assert!(has_solution(&hand([3, 3, 8, 8]), 24, JokerRule::Excluded));
+    assert!(has_solution(&hand([12, 12, 12, 12]), 24, JokerRule::Excluded));
+    assert!(!has_solution(&hand([1, 1, 1, 1]), 24, JokerRule::Excluded));
+    assert!(solve(&hand([1, 1, 1, 1]), 24, JokerRule::Excluded, usize::MAX, None).is_empty());
+}
+
+#[test]
+fn solve_limit_test() {
+    let hand: Vec<Card> = [1, 2, 3, 4, 5, 6].iter()
+        .map(|value| Card::new(crate::common::CardType::Cup, *value)).collect();
+    let solutions = solve(&hand, 24, JokerRule::Excluded, 3, None);
+    assert_eq!(solutions.len(), 3);
+    for solution in solutions.iter() {
+        assert_eq!(crate::evaluator::eval(solution), Ok(Fraction::from(24)));
+    }
+}
+
+#[test]
+fn wild_joker_test() {
+    use crate::common::CardType;
+    let mut cards = hand([1, 1, 1, 1]);
+    cards[0] = Card::new(CardType::Joker, 0);
+    assert!(!has_solution(&cards, 24, JokerRule::Excluded));
+    assert!(has_solution(&cards, 24, JokerRule::Fixed(12)));
+    assert!(has_solution(&cards, 24, JokerRule::Wild(12)));
+    let solutions: Vec<String> = solve(&cards, 24, JokerRule::Wild(12), usize::MAX, None).iter().map(Expr::to_string).collect();
+    assert!(solutions.contains(&"J=8*(1+1+1)".to_string()));
+    for solution in solutions.iter() {
+        let expr = crate::answer::parse(solution).unwrap();
+        assert_eq!(crate::answer::check_cards(&expr, &cards, JokerRule::Wild(12)), Ok(()));
+    }
+}
+
+#[test]
+fn has_solution_before_test() {
+    let big: Vec<Card> = [1, 2, 3, 5, 7, 11].iter()
+        .map(|value| Card::new(crate::common::CardType::Cup, *value)).collect();
+    let start = Instant::now();
+    assert_eq!(has_solution_before(&big, 997, JokerRule::Excluded, Some(start)), None);
+    assert!(start.elapsed().as_millis() < 100);
+    assert_eq!(has_solution_before(&hand([3, 3, 8, 8]), 24, JokerRule::Excluded, None), Some(true));
+    assert_eq!(has_solution_before(&hand([1, 1, 1, 1]), 24, JokerRule::Excluded, None), Some(false));
+
+    // listing the solutions stops at the deadline too, with what it has
+    let start = Instant::now();
+    for solution in solve(&big, 24, JokerRule::Excluded, usize::MAX, Some(start)) {
+        assert_eq!(crate::answer::check_cards(&solution, &big, JokerRule::Excluded), Ok(()));
+    }
+    assert!(start.elapsed().as_millis() < 100);
+}
@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::common::{FromServerMessage, HandCardData, Rejection, ScoreEntry, TableConfig, TableInfo, TurnEndType};
+use crate::core_cards::{Claim, Deal, Game24, TurnResult};
+use crate::round::Round;
+use crate::score;
+
+// What a table wants the server to send, and to which client: the table knows
+// its players by the ids the server gave them, not by their connections
+pub type Outgoing = Vec<(usize, FromServerMessage)>;
+
+// One independent game: its own deck, its own players and its own rounds
+pub struct Table {
+    name:       String,
+    config:     TableConfig,
+    game:       Game24,
+    round:      Option<Round>,
+    players:    HashMap<usize, usize>,     // seat of each client id in the game
+    nicknames:  Vec<String>,               // who sat at each seat, even if gone
+    finished:   bool,                      // the deck could not give another hand
+}
+
+impl Table {
+    pub fn new(name: String, config: TableConfig) -> Self {
+        Table {
+            name,
+            game: Game24::new(config.clone()),
+            config,
+            round: None,
+            players: HashMap::new(),
+            nicknames: Vec::new(),
+            finished: false,
+        }
+    }
+
+    pub fn info(&self) -> TableInfo {
+        TableInfo {
+            name: self.name.clone(),
+            players: self.players.len(),
+            config: self.config.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+
+    // A player coming back under the same nickname gets its empty seat again,
+    // with the cards it had won. Everybody sees who sits at the table now
+    pub fn join(&mut self, client: usize, nickname: &str) -> Outgoing {
+        if self.players.contains_key(&client) {
+            return vec![];
+        }
+        let taken: Vec<usize> = self.players.values().copied().collect();
+        let seat = match (0..self.nicknames.len()).find(|seat| self.nicknames[*seat] == nickname && !taken.contains(seat)) {
+            Some(seat) => seat,
+            None => {
+                self.nicknames.push(nickname.to_string());
+                self.game.add_player()
+            }
+        };
+        self.players.insert(client, seat);
+        self.scoreboard_to_all()
+    }
+
+    // The seat keeps its cards, only the name shown for it changes
+    pub fn rename(&mut self, client: usize, nickname: &str) -> Outgoing {
+        match self.players.get(&client) {
+            Some(seat) => {
+                self.nicknames[*seat] = nickname.to_string();
+                self.scoreboard_to_all()
+            }
+            None => vec![],
+        }
+    }
+
+    // The cards won stay in the game, the seat is just left empty.
+    // If everybody still sitting had passed, the leaving player closes the tie
+    pub fn leave(&mut self, client: usize, at: Instant) -> Outgoing {
+        if self.players.remove(&client).is_none() {
+            return vec![];
+        }
+        match self.round.as_ref() {
+            Some(round) if round.is_open() && !self.players.is_empty()
+                && round.all_passed(self.players.values()) => self.tie(at),
+            _ => self.scoreboard_to_all(),
+        }
+    }
+
+    pub fn scoreboard(&self) -> Vec<ScoreEntry> {
+        score::ranking(&self.nicknames, &self.game.cards_won())
+    }
+
+    // The player list of every panel at the table, sent whenever it changes
+    fn scoreboard_to_all(&self) -> Outgoing {
+        let scoreboard = self.scoreboard();
+        self.players.keys()
+            .map(|client| (*client, FromServerMessage::Scoreboard(scoreboard.clone())))
+            .collect()
+    }
+
+    // A new deck for the players still sitting, the ones gone leave no seat
+    fn restart(&mut self) {
+        self.game = Game24::new(self.config.clone());
+        self.round = None;
+        let old_nicknames = std::mem::take(&mut self.nicknames);
+        for seat in self.players.values_mut() {
+            self.nicknames.push(old_nicknames[*seat].clone());
+            *seat = self.game.add_player();
+        }
+        self.finished = false;
+    }
+
+    fn game_over_message(&self) -> FromServerMessage {
+        FromServerMessage::GameOver { ranking: self.scoreboard(), unclaimed: self.game.unclaimed() }
+    }
+
+    fn game_over(&mut self) -> Outgoing {
+        self.finished = true;
+        println!("[{}] game over, {} cards unclaimed", self.name, self.game.unclaimed());
+        self.players.keys().map(|client| (*client, self.game_over_message())).collect()
+    }
+
+    // The hand goes to the pot and every player learns how big it is now
+    fn tie(&mut self, at: Instant) -> Outgoing {
+        if let Some(round) = self.round.as_mut() {
+            round.tie(at);
+            println!("[{}] round {} is a tie", self.name, round.number());
+        }
+        self.game.end_turn(TurnResult::Tie);
+        let pot = self.game.pot();
+        let mut outgoing: Outgoing = self.players.keys()
+            .map(|client| (*client, FromServerMessage::TurnEnd(TurnEndType::Tie(pot))))
+            .collect();
+        outgoing.append(&mut self.scoreboard_to_all());
+        outgoing
+    }
+
+    // Called every second: ends the rounds out of time as a tie and
+    // tells the players how long the others still have
+    pub fn tick(&mut self, now: Instant) -> Outgoing {
+        let round = match self.round.as_ref() {
+            Some(round) if round.is_open() => round,
+            _ => return vec![],
+        };
+        if round.expired(now) {
+            return self.tie(now);
+        }
+        match round.countdown(now) {
+            Some(countdown) => self.players.keys()
+                .map(|client| (*client, FromServerMessage::TimeLeft(countdown)))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn pass(&mut self, client: usize, at: Instant) -> Outgoing {
+        let seat = self.players.get(&client).copied();
+        let (seat, round) = match (seat, self.round.as_mut()) {
+            (Some(seat), Some(round)) if round.in_time(at) => (seat, round),
+            _ => return Table::too_late(client),
+        };
+        if !round.pass(seat) {
+            return vec![];
+        }
+        if round.all_passed(self.players.values()) {
+            return self.tie(at);
+        }
+        let message = format!("{} passes ({}/{})", self.nicknames[seat], round.passes(), self.players.len());
+        self.players.keys()
+            .map(|client| (*client, FromServerMessage::SendMsg(message.clone())))
+            .collect()
+    }
+
+    fn too_late(client: usize) -> Outgoing {
+        vec![(client, FromServerMessage::Rejected(vec![Rejection::TooLate]))]
+    }
+
+    // The winner is told so and everybody else at the table learns it lost the round
+    fn announce_winner(&self, winner: usize, seat: usize) -> Outgoing {
+        let scoreboard = self.scoreboard();
+        let mut outgoing = Outgoing::new();
+        for client in self.players.keys() {
+            let end = if *client == winner {
+                TurnEndType::YouWin
+            } else {
+                TurnEndType::OtherWin(self.nicknames[seat].clone())
+            };
+            outgoing.push((*client, FromServerMessage::TurnEnd(end)));
+            outgoing.push((*client, FromServerMessage::Scoreboard(scoreboard.clone())));
+        }
+        outgoing
+    }
+
+    pub fn new_turn(&mut self, client: usize) -> Outgoing {
+        let seat = match self.players.get(&client) {
+            Some(seat) => *seat,
+            None => return vec![],
+        };
+        match self.round.as_mut() {
+            Some(round) if round.is_open() => {
+                // a late comer gets the hand that is already on the table
+                if round.deal_to(seat) {
+                    let countdown = round.countdown(Instant::now());
+                    vec![(client, FromServerMessage::TurnBegin { round: round.number(), hand: round.hand().to_vec(), target: self.config.target, jokers: self.config.jokers, countdown })]
+                } else {
+                    vec![]
+                }
+            }
+            // the game over stays until a player asks for a new game
+            _ if self.finished => vec![(client, self.game_over_message())],
+            _ => {
+                let deal = self.game.give_cards();
+                if deal == Deal::Exhausted {
+                    return self.game_over();
+                }
+                let cards: HandCardData = self.game.hand();
+
+                println!("[{}] turn {}", self.name, self.game.turn_num());
+                for card in cards.iter() {
+                    println!("{:?}", card);
+                }
+
+                let limit = self.config.round_secs.map(Duration::from_secs);
+                let mut round = Round::begin(self.game.turn_num(), cards.clone(), limit);
+                let countdown = round.countdown(Instant::now());
+                let (number, target, jokers) = (round.number(), self.config.target, self.config.jokers);
+                let mut outgoing = Outgoing::new();
+                for (client, seat) in self.players.iter() {
+                    round.deal_to(*seat);
+                    outgoing.push((*client, FromServerMessage::TurnBegin { round: number, hand: cards.clone(), target, jokers, countdown }));
+                    if deal == Deal::Unchecked {
+                        outgoing.push((*client, FromServerMessage::SendMsg(
+                            "this hand may have no solution, claim it with none if you think so".to_string())));
+                    }
+                }
+                self.round = Some(round);
+                outgoing
+            }
+        }
+    }
+
+    // Only a finished table starts over, a game in play goes on
+    pub fn new_game(&mut self, client: usize) -> Outgoing {
+        let mut outgoing = Outgoing::new();
+        if self.finished {
+            self.restart();
+            outgoing = self.scoreboard_to_all();
+        }
+        outgoing.append(&mut self.new_turn(client));
+        outgoing
+    }
+
+    pub fn answer(&mut self, client: usize, answer: &str, at: Instant) -> Outgoing {
+        let seat = self.players.get(&client).copied();
+        let (seat, round) = match (seat, self.round.as_mut()) {
+            (Some(seat), Some(round)) if round.in_time(at) => (seat, round),
+            _ => return Table::too_late(client),
+        };
+
+        match self.game.make_answer(seat, answer) {
+            Ok(()) => {
+                if let Some(took) = round.close(seat, at) {
+                    println!("[{}] round {} won by {} in {:?}", self.name, round.number(), self.nicknames[seat], took);
+                }
+                self.announce_winner(client, seat)
+            }
+            Err(e) => {
+                println!("@ {}", e);
+                vec![(client, FromServerMessage::Rejected(e.rejections()))]
+            }
+        }
+    }
+
+    pub fn claim_no_solution(&mut self, client: usize, at: Instant) -> Outgoing {
+        let seat = self.players.get(&client).copied();
+        let (seat, round) = match (seat, self.round.as_mut()) {
+            (Some(seat), Some(round)) if round.in_time(at) => (seat, round),
+            _ => return Table::too_late(client),
+        };
+
+        let message = match self.game.claim_no_solution(seat) {
+            Claim::Accepted => {
+                round.close(seat, at);
+                return self.announce_winner(client, seat);
+            }
+            Claim::Rejected => FromServerMessage::SendMsg(
+                "this hand has a solution, one of your cards goes to the pot".to_string()),
+            Claim::NotAllowed => FromServerMessage::SendMsg(
+                "hands without solution are redealt at this table".to_string()),
+            Claim::Repeated => FromServerMessage::SendMsg(
+                "you already claimed this hand".to_string()),
+        };
+        vec![(client, message)]
+    }
+}
+
+// Eight sixes, two hands of 6+6+6+6 and the deck is done
+#[cfg(test)]
+fn sixes() -> TableConfig {
+    use crate::common::{Card, CardType, DeckKind};
+    let cards = vec![Card::new(CardType::Cup, 6); 8];
+    TableConfig { deck: DeckKind::Custom { name: "sixes".to_string(), cards }, ..TableConfig::default() }
+}
+
+// A table with the clients 0, 1... sitting as player0, player1...
+#[cfg(test)]
+fn seated(config: TableConfig, players: usize) -> Table {
+    let mut table = Table::new("test".to_string(), config);
+    for client in 0..players {
+        table.join(client, &format!("player{}", client));
+    }
+    table
+}
+
+#[cfg(test)]
+fn sent_to(outgoing: &Outgoing, client: usize) -> Vec<&FromServerMessage> {
+    outgoing.iter().filter(|(to, _)| *to == client).map(|(_, message)| message).collect()
+}
+
+#[test]
+fn join_leave_test() {
+    let mut table = seated(TableConfig::default(), 1);
+    // everybody at the table gets the new player list
+    let outgoing = table.join(1, "player1");
+    assert_eq!(outgoing.len(), 2);
+    assert!(outgoing.iter().all(|(_, message)| matches!(message, FromServerMessage::Scoreboard(ranking) if ranking.len() == 2)));
+    assert!(table.join(0, "again").is_empty());
+    assert_eq!(table.info().players, 2);
+    assert_eq!(table.scoreboard().len(), 2);
+    assert!(matches!(table.leave(0, Instant::now())[..], [(1, FromServerMessage::Scoreboard(_))]));
+    assert!(table.leave(0, Instant::now()).is_empty());
+    assert!(table.leave(1, Instant::now()).is_empty());
+    assert!(table.is_empty());
+    // the seats of the gone players stay in the ranking
+    assert_eq!(table.scoreboard().len(), 2);
+}
+
+#[test]
+fn rejoin_test() {
+    let mut table = seated(sixes(), 2);
+    table.new_turn(0);
+    table.answer(0, "6+6+6+6", Instant::now());
+    table.leave(0, Instant::now());
+    // the same player on a new connection sits where it sat
+    table.join(5, "player0");
+    assert_eq!(table.info().players, 2);
+    assert_eq!(table.scoreboard(), vec![
+        ScoreEntry { rank: 1, nickname: "player0".to_string(), cards: 4 },
+        ScoreEntry { rank: 2, nickname: "player1".to_string(), cards: 0 },
+    ]);
+    // a seat in use is not given away
+    table.join(6, "player1");
+    assert_eq!(table.scoreboard().len(), 3);
+}
+
+#[test]
+fn rename_test() {
+    let mut table = seated(sixes(), 2);
+    table.new_turn(0);
+    table.answer(0, "6+6+6+6", Instant::now());
+    let outgoing = table.rename(0, "winner");
+    assert!(matches!(&outgoing[..], [(_, FromServerMessage::Scoreboard(ranking)), _] if ranking[0].nickname == "winner"));
+    assert!(table.rename(9, "nobody").is_empty());
+    assert_eq!(table.scoreboard()[0], ScoreEntry { rank: 1, nickname: "winner".to_string(), cards: 4 });
+    assert_eq!(table.scoreboard()[1].nickname, "player1");
+    table.new_turn(0);
+    let outgoing = table.pass(0, Instant::now());
+    assert!(matches!(&outgoing[0].1, FromServerMessage::SendMsg(text) if text == "winner passes (1/2)"));
+}
+
+#[test]
+fn new_turn_test() {
+    let mut table = seated(TableConfig::default(), 2);
+    let outgoing = table.new_turn(0);
+    assert_eq!(outgoing.len(), 2);
+    assert!(outgoing.iter().all(|(_, message)| matches!(message, FromServerMessage::TurnBegin { round: 1, .. })));
+    // the hand is already on the table, nobody gets it twice
+    assert!(table.new_turn(1).is_empty());
+    // a late comer gets it once
+    table.join(2, "late");
+    assert!(matches!(sent_to(&table.new_turn(2), 2)[..], [FromServerMessage::TurnBegin { round: 1, .. }]));
+    assert!(table.new_turn(2).is_empty());
+    // somebody not at the table gets nothing
+    assert!(table.new_turn(9).is_empty());
+}
+
+#[test]
+fn answer_test() {
+    let mut table = seated(sixes(), 2);
+    table.new_turn(0);
+    let outgoing = table.answer(1, "6+6+6", Instant::now());
+    assert!(matches!(outgoing[..], [(_, FromServerMessage::Rejected(_))]));
+    assert_eq!(outgoing[0].0, 1);
+
+    let outgoing = table.answer(1, "6+6+6+6", Instant::now());
+    assert!(matches!(sent_to(&outgoing, 1)[..], [FromServerMessage::TurnEnd(TurnEndType::YouWin), FromServerMessage::Scoreboard(_)]));
+    match sent_to(&outgoing, 0)[..] {
+        [FromServerMessage::TurnEnd(TurnEndType::OtherWin(winner)), FromServerMessage::Scoreboard(ranking)] => {
+            assert_eq!(winner, "player1");
+            assert_eq!(ranking[0], ScoreEntry { rank: 1, nickname: "player1".to_string(), cards: 4 });
+        }
+        _ => panic!("the loser is told who won"),
+    }
+    // the round is closed for the one who comes second
+    assert!(matches!(table.answer(0, "6+6+6+6", Instant::now())[..], [(_, FromServerMessage::Rejected(ref reasons))] if reasons[..] == [Rejection::TooLate]));
+}
+
+#[test]
+fn deadline_test() {
+    // the tick that closes the round has not come, the answer is late anyway
+    let mut table = seated(TableConfig { round_secs: Some(1), ..sixes() }, 2);
+    table.new_turn(0);
+    let late = Instant::now() + Duration::from_secs(5);
+    let outgoing = table.answer(1, "6+6+6+6", late);
+    assert!(matches!(&outgoing[..], [(1, FromServerMessage::Rejected(reasons))] if reasons[..] == [Rejection::TooLate]));
+    assert!(matches!(&table.pass(1, late)[..], [(1, FromServerMessage::Rejected(_))]));
+    assert!(matches!(table.answer(1, "6+6+6+6", Instant::now())[..], [_, _, _, _]));
+}
+
+#[test]
+fn pass_test() {
+    let mut table = seated(TableConfig::default(), 2);
+    table.new_turn(0);
+    let outgoing = table.pass(0, Instant::now());
+    assert_eq!(outgoing.len(), 2);
+    assert!(outgoing.iter().all(|(_, message)| matches!(message, FromServerMessage::SendMsg(text) if text == "player0 passes (1/2)")));
+    // passing twice counts once
+    assert!(table.pass(0, Instant::now()).is_empty());
+    let outgoing = table.pass(1, Instant::now());
+    for client in 0..2 {
+        assert!(matches!(sent_to(&outgoing, client)[..], [FromServerMessage::TurnEnd(TurnEndType::Tie(4)), FromServerMessage::Scoreboard(_)]));
+    }
+}
+
+#[test]
+fn leave_tie_test() {
+    // everybody still sitting passed, the one leaving closes the tie
+    let mut table = seated(TableConfig::default(), 2);
+    table.new_turn(0);
+    table.pass(0, Instant::now());
+    let outgoing = table.leave(1, Instant::now());
+    assert!(matches!(outgoing[..], [(0, FromServerMessage::TurnEnd(TurnEndType::Tie(4))), (0, FromServerMessage::Scoreboard(_))]));
+}
+
+#[test]
+fn game_over_test() {
+    let mut table = seated(sixes(), 2);
+    for _ in 0..2 {
+        table.new_turn(0);
+        table.answer(0, "6+6+6+6", Instant::now());
+    }
+    let outgoing = table.new_turn(1);
+    assert_eq!(outgoing.len(), 2);
+    assert!(outgoing.iter().all(|(_, message)| matches!(message, FromServerMessage::GameOver { unclaimed: 0, .. })));
+    // the game over stays for whoever asks for a turn
+    assert!(matches!(table.new_turn(1)[..], [(_, FromServerMessage::GameOver { .. })]));
+    // only a new game deals again, from a full deck, and the panels show it
+    let outgoing = table.new_game(1);
+    for client in 0..2 {
+        match sent_to(&outgoing, client)[..] {
+            [FromServerMessage::Scoreboard(ranking), FromServerMessage::TurnBegin { round: 1, .. }] =>
+                assert!(ranking.iter().all(|entry| entry.cards == 0)),
+            _ => panic!("a new game shows the scores and deals"),
+        }
+    }
+}
+
+#[test]
+fn new_game_in_play_test() {
+    // a game in play is not restarted, the next hand comes from the same deck
+    let mut table = seated(sixes(), 2);
+    table.new_turn(0);
+    table.answer(0, "6+6+6+6", Instant::now());
+    let outgoing = table.new_game(1);
+    assert_eq!(outgoing.len(), 2);
+    assert!(outgoing.iter().all(|(_, message)| matches!(message, FromServerMessage::TurnBegin { round: 2, .. })));
+    assert_eq!(table.scoreboard()[0].cards, 4);
+}
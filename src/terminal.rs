@@ -1,649 +1,465 @@
 extern crate termion;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
 use std::io;
 use std::io::{Write, Stdout};
-use super::common::{HandCardData, CARDCOUNT, Card, CardType};
-use self::termion::input::MouseTerminal;
+use super::common::{HandCardData, Card, CardType, CARDCOUNT, Countdown, JokerRule, ScoreEntry};
+use std::panic;
 use self::termion::raw::{IntoRawMode, RawTerminal};
+use self::termion::screen::{AlternateScreen, IntoAlternateScreen, ToMainScreen};
+use self::termion::{color, style};
+use crate::evaluator::Fraction;
+use crate::theme::{Theme, CARD_HEIGHT, CARD_WIDTH};
+
+// The terminal while the client runs: raw input on the alternate screen.
+// Dropping it gives the player back the terminal as it was
+pub struct Session {
+    screen: AlternateScreen<RawTerminal<Stdout>>,
+}
+
+impl Session {
+    pub fn start() -> io::Result<Self> {
+        let screen = io::stdout().into_raw_mode()?.into_alternate_screen()?;
+        // the message of a panic goes to the main screen where it can be read,
+        // unwinding drops the session and ends raw mode
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            print!("{}{}", ToMainScreen, termion::cursor::Show);
+            previous(info);
+        }));
+        Ok(Session { screen })
+    }
+}
+
+impl Write for Session {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.screen.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.screen.flush()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        write!(self.screen, "{}{}", style::Reset, termion::cursor::Show).unwrap_or(());
+        self.screen.flush().unwrap_or(());
+    }
+}
 
 pub struct VisualDeck{
-    pub stdout: MouseTerminal<RawTerminal<Stdout>>,
-    back:   Vec<&'static str>,
-    fronts: HashMap<Card, Vec<&'static str>>
+    pub stdout: Session,
+    back:   Vec<String>,
+    fronts: HashMap<(CardType, u8), Vec<String>>,   // the ones in the theme, the rest are plain
+    color:  bool,   // false paints everything in the terminal's own colour
+    size:   (u16, u16),             // of the terminal when the layout was made
+    layout: Layout,
+    status: Status,
+    panel_rows: u16,                // drawn the last time, the ones left over are cleared
+}
+
+// Events kept in the panel, the oldest go first
+const LOG_LINES: usize = 6;
+
+// What the client knows besides the hand, all of it drawn again after a resize
+#[derive(Default)]
+struct Status {
+    connection: String,
+    round: Option<u8>,
+    scoreboard: Vec<ScoreEntry>,
+    message: String,            // the last one from the server, under the prompt
+    log: VecDeque<String>,
+}
+
+// NO_COLOR (https://no-color.org) or a terminal that is not one turn colours off
+fn supports_color() -> bool {
+    !matches!(std::env::var_os("NO_COLOR"), Some(value) if !value.is_empty())
+        && std::env::var("TERM").is_ok_and(|term| term != "dumb")
+        && termion::is_tty(&io::stdout())
+}
+
+fn suit_color(suit: CardType) -> String {
+    match suit {
+        CardType::Gold => color::Fg(color::Yellow).to_string(),
+        CardType::Cup | CardType::Heart | CardType::Diamond => color::Fg(color::Red).to_string(),
+        CardType::Sword => color::Fg(color::Blue).to_string(),
+        CardType::Club => color::Fg(color::Green).to_string(),
+        CardType::Joker => color::Fg(color::Magenta).to_string(),
+        CardType::Clover | CardType::Spade => String::new(),
+    }
+}
+
+// Escapes written before the border and the inside of a card, the reset
+// after them is written anyway
+struct Paint {
+    border: String,
+    face: String,
+}
+
+// Space taken by a card and the gap to the next one
+const CARD_STEP: (u16, u16) = (CARD_WIDTH as u16 + 4, CARD_HEIGHT as u16 + 1);
+// Columns needed right of the hand for the countdown bar and its label
+const BAR_ROOM: u16 = 4;
+// Columns of the status panel when it goes beside the hand
+const PANEL_WIDTH: u16 = 32;
+// Rows under the hand: the target, a blank one, the prompt, its value and the message
+const TEXT_ROWS: u16 = 6;
+
+// Where everything goes for a hand on a terminal of a given size
+#[derive(PartialEq, Eq, Debug)]
+struct Layout {
+    cards: Vec<(u16, u16)>,     // top left corner of each card, empty to write them in one line
+    side: u16,                  // first column right of the hand
+    bar_height: u16,            // 0 for a countdown in text
+    panel: (u16, u16),
+    panel_width: u16,
+    target_row: u16,
+    prompt_row: u16,
+}
+
+impl Layout {
+    // As many cards in a row as the width allows, then as many rows as needed;
+    // if they do not fit they are written as `[7♣] [12†]`
+    fn new((width, height): (u16, u16), count: usize) -> Self {
+        let count = count.max(1) as u16;
+        let max_columns = width.saturating_sub(2 + BAR_ROOM) / CARD_STEP.0;
+        let rows = if max_columns > 0 { count.div_ceil(max_columns) } else { 0 };
+        if rows == 0 || 2 + rows * CARD_STEP.1 + TEXT_ROWS > height {
+            let target_row = 5;
+            return Layout {
+                cards: vec![],
+                side: 2,
+                bar_height: 0,
+                panel: (2, target_row + TEXT_ROWS + 1),
+                panel_width: width.saturating_sub(2),
+                target_row,
+                prompt_row: target_row + 2,
+            };
+        }
+        // the same number of cards in every row as far as possible
+        let columns = count.div_ceil(rows);
+        let cards = (0..count)
+            .map(|i| (2 + (i % columns) * CARD_STEP.0, 2 + (i / columns) * CARD_STEP.1))
+            .collect();
+        let side = columns * CARD_STEP.0;
+        let target_row = 2 + rows * CARD_STEP.1 + 1;
+        let (panel, panel_width) = if width >= side + BAR_ROOM + PANEL_WIDTH {
+            ((side + BAR_ROOM, 2), PANEL_WIDTH)
+        } else {
+            ((2, target_row + TEXT_ROWS + 1), width.saturating_sub(2))
+        };
+        Layout {
+            cards,
+            side,
+            bar_height: rows * CARD_STEP.1 - 2,
+            panel,
+            panel_width,
+            target_row,
+            prompt_row: target_row + 2,
+        }
+    }
+}
+
+fn terminal_size() -> (u16, u16) {
+    termion::terminal_size().unwrap_or((80, 24))
 }
 
 impl VisualDeck {
-    pub fn new() -> Self {
-        let mut me = VisualDeck {
-            stdout: MouseTerminal::from(io::stdout().into_raw_mode().unwrap()),
-            back : card_str_back(),
-            fronts : HashMap::<Card, Vec<&'static str>>::new()
+    pub fn new(theme: Theme) -> Self {
+        VisualDeck {
+            stdout: Session::start().unwrap(),
+            back : theme.back,
+            fronts : theme.fronts,
+            color: supports_color(),
+            size: (0, 0),
+            layout: Layout::new(terminal_size(), CARDCOUNT),
+            status: Status::default(),
+            panel_rows: 0,
+        }
+    }
+
+    // Without colour the suit can not be told apart, but the used cards still
+    // stand out with their border inverted
+    fn paint(&self, card: &Card, used: bool) -> Paint {
+        let face = if self.color { suit_color(card._type) } else { String::new() };
+        let border = match (used, self.color) {
+            (false, _) => face.clone(),
+            (true, true) => format!("{}{}", style::Bold, color::Fg(color::LightCyan)),
+            (true, false) => style::Invert.to_string(),
         };
+        Paint { border, face }
+    }
 
-        load_cards_str_front(& mut me);
+    // A new size or another number of cards clear the screen and start over
+    fn relayout(& mut self, count: usize) {
+        let size = terminal_size();
+        if size != self.size || (!self.layout.cards.is_empty() && self.layout.cards.len() != count) {
+            self.size = size;
+            self.layout = Layout::new(size, count);
+            write!(self.stdout, "{}", termion::clear::All).unwrap();
+            self.panel_rows = 0;
+            self.draw_panel();
+            let message = std::mem::take(&mut self.status.message);
+            self.draw_message(&message);
+        }
+    }
 
-        me
+    // The cards face down, while the table waits for the first deal
+    pub fn draw_backs(& mut self, count: usize) {
+        self.relayout(count);
+        let paint = Paint { border: String::new(), face: String::new() };
+        for position in self.layout.cards.iter() {
+            draw_card(&self.back, & mut self.stdout, position, &paint);
+        }
+        self.stdout.flush().unwrap();
     }
 
-    pub fn add(&mut self, _type: CardType, value: u8, visual: Vec<&'static str>) {
+    // `used` tells the cards that the answer being typed already takes
+    pub fn draw_hand(& mut self, hand: &HandCardData, used: &[bool]) {
+        self.relayout(hand.len());
 
-        self.fronts.insert( Card{ _type, value } , visual);
+        if self.layout.cards.is_empty() {
+            write!(self.stdout, "{}", termion::cursor::Goto(2, 2)).unwrap();
+            for (i, card) in hand.iter().enumerate() {
+                let paint = self.paint(card, used.get(i).copied().unwrap_or(false));
+                write!(self.stdout, "{}[{}{}]{} ", paint.border, rank_label(card), suit_symbol(card._type), style::Reset).unwrap();
+            }
+            write!(self.stdout, "{}", termion::clear::UntilNewline).unwrap();
+        }
+        for (i, (card, position)) in hand.iter().zip(self.layout.cards.iter()).enumerate() {
+            let paint = self.paint(card, used.get(i).copied().unwrap_or(false));
+            let visual = match self.fronts.get( &(card._type, card.value) ) {
+                Some(visual) => visual.clone(),
+                None => plain_card(card),
+            };
+            draw_card(
+                &visual,
+                & mut self.stdout,
+                position,
+                &paint
+            );
+        }
 
+        self.stdout.flush().unwrap();
     }
 
-    pub fn draw_hand(& mut self, hand: &HandCardData) {
+    // The connection and the round, the cards won by each player and the last
+    // events, beside the hand or under it if there is no room
+    fn draw_panel(& mut self) {
+        let (x, y) = self.layout.panel;
+        let width = self.layout.panel_width as usize;
+        let mut lines = vec![match self.status.round {
+            Some(round) => format!("{} · round {}", self.status.connection, round),
+            None => self.status.connection.clone(),
+        }];
+        lines.extend(self.status.scoreboard.iter().map(ranking_line));
+        lines.push(String::new());
+        lines.extend(self.status.log.iter().cloned());
+        let rows = lines.len() as u16;
+        lines.resize(self.panel_rows.max(rows) as usize, String::new());
+        for (row, line) in (y..).zip(lines.iter()) {
+            let line: String = line.chars().take(width).collect();
+            write!(self.stdout, "{}{:<width$}", termion::cursor::Goto(x, row), line, width = width).unwrap();
+        }
+        self.panel_rows = rows;
         self.stdout.flush().unwrap();
-        let mut positions = Vec::<(u16, u16)>::new();
-        positions.push((2, 2));
-        positions.push((20, 2));
-        positions.push((2, 12));
-        positions.push((20, 12));
-
-        let mut pos_iter = positions.iter();
-        for i in 0..CARDCOUNT {
-            if let Some(pos) = pos_iter.next() {
-                draw_card(
-                    & self.fronts.get( & hand[i] ).unwrap(),
-                    & mut self.stdout,
-                    pos
-                );
+    }
+
+    pub fn set_connection(& mut self, connection: &str) {
+        self.status.connection = connection.to_string();
+        self.draw_panel();
+    }
+
+    pub fn set_round(& mut self, round: u8) {
+        self.status.round = Some(round);
+        self.draw_panel();
+    }
+
+    // Something that happened at the table, kept with the last few ones
+    pub fn log(& mut self, event: &str) {
+        self.status.log.push_back(event.to_string());
+        if self.status.log.len() > LOG_LINES {
+            self.status.log.pop_front();
+        }
+        self.draw_panel();
+    }
+
+    pub fn draw_scoreboard(& mut self, scoreboard: &[ScoreEntry]) {
+        self.status.scoreboard = scoreboard.to_vec();
+        self.draw_panel();
+    }
+
+    // The answer being typed with the numbers no card can take marked, under
+    // it what it makes so far, and the cursor left where the editor has it
+    pub fn draw_prompt(& mut self, typed: &str, cursor: usize, unmatched: &[Range<usize>], value: Option<Fraction>) {
+        let row = self.layout.prompt_row;
+        let mark = if self.color { color::Fg(color::Red).to_string() } else { style::Underline.to_string() };
+        write!(self.stdout, "{}>> ", termion::cursor::Goto(2, row)).unwrap();
+        for (i, c) in typed.chars().enumerate() {
+            if unmatched.iter().any(|range| range.contains(&i)) {
+                write!(self.stdout, "{}{}{}", mark, c, style::Reset).unwrap();
+            } else {
+                write!(self.stdout, "{}", c).unwrap();
             }
+        }
+        write!(self.stdout, "{}", termion::clear::UntilNewline).unwrap();
+        write!(self.stdout, "{}{}", termion::cursor::Goto(2, row + 1), termion::clear::CurrentLine).unwrap();
+        if let Some(value) = value {
+            write!(self.stdout, "   = {}", value).unwrap();
+        }
+        write!(self.stdout, "{}", termion::cursor::Goto(5 + cursor as u16, row)).unwrap();
+    }
+
+    // A line under the prompt for what the player should know now
+    pub fn draw_message(& mut self, message: &str) {
+        let row = self.layout.prompt_row + 2;
+        write!(self.stdout, "{}{}{}", termion::cursor::Goto(2, row), termion::clear::CurrentLine, message).unwrap();
+        self.status.message = message.to_string();
+        self.stdout.flush().unwrap();
+    }
+
+    // What the answers have to make this round, above the prompt
+    pub fn draw_target(& mut self, target: i64, jokers: JokerRule) {
+        write!(self.stdout, "{}make {} with the cards", termion::cursor::Goto(2, self.layout.target_row), target).unwrap();
+        match jokers {
+            JokerRule::Excluded => (),
+            JokerRule::Wild(_) => write!(self.stdout, ", a joker is written J=<value>").unwrap(),
+            JokerRule::Fixed(value) => write!(self.stdout, ", a joker is worth {}", value).unwrap(),
+        }
+    }
 
+    // A bar beside the hand that empties as the round runs out, or
+    // the seconds under the cards when they are written in a line
+    pub fn draw_countdown(& mut self, countdown: &Countdown) {
+        let (x, height) = (self.layout.side, self.layout.bar_height as u64);
+        if height == 0 {
+            write!(self.stdout, "{}{:>3}s left{}", termion::cursor::Goto(2, 3), countdown.left, termion::clear::UntilNewline).unwrap();
+            return;
+        }
+        let full = (countdown.left * height).checked_div(countdown.total).unwrap_or(0);
+        for row in 0..height {
+            let cell = if height - row <= full { "█" } else { "░" };
+            write!(self.stdout, "{}{}", termion::cursor::Goto(x, 2 + row as u16), cell).unwrap();
         }
+        write!(self.stdout, "{}{:>3}s", termion::cursor::Goto(x - 2, 3 + height as u16), countdown.left).unwrap();
+    }
 
+    pub fn draw_game_over(& mut self, ranking: &[ScoreEntry], unclaimed: usize) {
+        write!(self.stdout, "{}{}GAME OVER", termion::clear::All, termion::cursor::Goto(2, 2)).unwrap();
+        draw_ranking(& mut self.stdout, ranking, (2, 4));
+        let row = 5 + ranking.len() as u16;
+        write!(self.stdout, "{}{} cards left unclaimed", termion::cursor::Goto(2, row), unclaimed).unwrap();
+        write!(self.stdout, "{}press Enter for a new game", termion::cursor::Goto(2, row + 2)).unwrap();
         self.stdout.flush().unwrap();
+        // whatever comes next is drawn on a clean screen
+        self.size = (0, 0);
     }
 }
 
-fn draw_card(card_visual: &Vec<&'static str>, stdout: &mut MouseTerminal<RawTerminal<Stdout>>, (x, y): &(u16, u16)) {
-    let mut row = *y;
-    for str in card_visual {
-        write!(stdout, "{}{}", termion::cursor::Goto(*x, row), str).unwrap();
-        row +=1;
-    }
+fn ranking_line(entry: &ScoreEntry) -> String {
+    format!("{:>2}. {:<16} {:>3} cards", entry.rank, entry.nickname, entry.cards)
 }
 
+fn draw_ranking(stdout: &mut Session, ranking: &[ScoreEntry], (x, y): (u16, u16)) {
+    for (row, entry) in (y..).zip(ranking.iter()) {
+        write!(stdout, "{}{}", termion::cursor::Goto(x, row), ranking_line(entry)).unwrap();
+    }
+}
 
-macro_rules! make_str_card {
-    ( $( $x:expr ),* ) => {
-        {
-            let mut vec = Vec::<&'static str>::new();
-            $(
-                #[allow(unused_assignments)]
-                {
-                    vec.push($x);
-                }
-            )*
-            vec
+fn draw_card(card_visual: &[String], stdout: &mut Session, (x, y): &(u16, u16), paint: &Paint) {
+    let last = card_visual.len().saturating_sub(1);
+    for (i, (row, str)) in (*y..).zip(card_visual.iter()).enumerate() {
+        write!(stdout, "{}", termion::cursor::Goto(*x, row)).unwrap();
+        let mut chars = str.chars();
+        match (chars.next(), chars.next_back()) {
+            (Some(left), Some(right)) if i > 0 && i < last => write!(stdout, "{}{}{}{}{}{}{}{}{}",
+                paint.border, left, style::Reset, paint.face, chars.as_str(), style::Reset,
+                paint.border, right, style::Reset).unwrap(),
+            _ => write!(stdout, "{}{}{}", paint.border, str, style::Reset).unwrap(),
         }
-    };
+    }
 }
 
-fn card_str_back() -> Vec<&'static str>{
-    make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│╳╳╳╳╳╳╳╳╳╳╳╳│"#,
-        r#"│╳╳╳╳╳╳╳╳╳╳╳╳│"#,
-        r#"│╳╳╳╳╳╳╳╳╳╳╳╳│"#,
-        r#"│╳CARDASCII!╳│"#,
-        r#"│╳╳╳╳╳╳╳╳╳╳╳╳│"#,
-        r#"│╳╳╳╳╳╳╳╳╳╳╳╳│"#,
-        r#"│╳╳╳╳╳╳╳╳╳╳╳╳│"#,
-        r#"└────────────┘"#)
+fn suit_symbol(suit: CardType) -> char {
+    match suit {
+        CardType::Gold => 'o',
+        CardType::Club => 'I',
+        CardType::Sword => '†',
+        CardType::Cup => 'U',
+        CardType::Joker => '*',
+        CardType::Heart => '♥',
+        CardType::Diamond => '♦',
+        CardType::Clover => '♣',
+        CardType::Spade => '♠',
+    }
+}
+
+fn rank_label(card: &Card) -> String {
+    let french = matches!(card._type, CardType::Heart | CardType::Diamond | CardType::Clover | CardType::Spade);
+    match card.value {
+        1 if french => "A".to_string(),
+        11 if french => "J".to_string(),
+        12 if french => "Q".to_string(),
+        13 if french => "K".to_string(),
+        value => value.to_string(),
+    }
+}
+
+// Same size as the drawn ones: the rank and suit in the corners, the
+// suit in the middle and what the card counts if that is not its rank
+fn plain_card(card: &Card) -> Vec<String> {
+    let (rank, suit) = (rank_label(card), suit_symbol(card._type));
+    let worth = if card.worth != card.value { format!("={}", card.worth) } else { String::new() };
+    vec![
+        "┌────────────┐".to_string(),
+        format!("│{:<12}│", rank),
+        format!("│{:<12}│", suit),
+        format!("│{:^12}│", ""),
+        format!("│{:^12}│", suit),
+        format!("│{:^12}│", worth),
+        format!("│{:>12}│", suit),
+        format!("│{:>12}│", rank),
+        "└────────────┘".to_string(),
+    ]
+}
+
+#[test]
+fn layout_test() {
+    // a wide terminal takes them in a row, the panel beside them
+    let layout = Layout::new((110, 24), 4);
+    assert_eq!(layout.cards, vec![(2, 2), (20, 2), (38, 2), (56, 2)]);
+    assert_eq!((layout.side, layout.bar_height, layout.target_row, layout.prompt_row), (72, 8, 13, 15));
+    assert_eq!((layout.panel, layout.panel_width), ((76, 2), PANEL_WIDTH));
+
+    // a narrow one in two rows of two, the panel under them
+    let layout = Layout::new((60, 40), 4);
+    assert_eq!(layout.cards, vec![(2, 2), (20, 2), (2, 12), (20, 12)]);
+    assert_eq!((layout.side, layout.target_row), (36, 23));
+    assert_eq!((layout.panel, layout.panel_width), ((2, 30), 58));
+
+    // six cards in two rows of three, the panel goes under them
+    let layout = Layout::new((80, 40), 6);
+    assert_eq!(layout.cards.len(), 6);
+    assert_eq!(layout.cards[3], (2, 12));
+    assert_eq!(layout.panel, (2, layout.target_row + TEXT_ROWS + 1));
+
+    // too small for the art
+    let layout = Layout::new((40, 15), 4);
+    assert!(layout.cards.is_empty());
+    assert_eq!(layout.bar_height, 0);
 }
 
-fn load_cards_str_front(deck: & mut VisualDeck) {
-
-    deck.add(CardType::Joker, 0, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│J    ◔   ⊙  │"#,
-        r#"│O  ๏ |\  |\ │"#,
-        r#"│K  |\/ |/ | │"#,
-        r#"│E  ʕ  ͡o  ͡o| │"#,
-        r#"│R  °༽   ͜ʖ༼  │"#,
-        r#"│     ༽  ༼   │"#,
-        r#"│            │"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Joker, 0, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│J    ◔   ⊙  │"#,
-        r#"│O  ๏ |\  |\ │"#,
-        r#"│K  |\/ |/ | │"#,
-        r#"│E  ʕ  ͡o  ͡o| │"#,
-        r#"│R  °༽   ͜ʖ༼  │"#,
-        r#"│     ༽  ༼   │"#,
-        r#"│            │"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Sword, 12, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│12  /^^^┼^\ │"#,
-        r#"│|\ (  ° ͜ʖ° )│"#,
-        r#"│ \\ \     / │"#,
-        r#"│ _\\_---⚙-\ │"#,
-        r#"│   ฿   .๏. \│"#,
-        r#"│  /    .๏.  │"#,
-        r#"│ /     .๏.12│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 11, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│11    ┌──@─┐│"#,
-        r#"│|\    (° ͜ʖ°)│"#,
-        r#"│ \\   /    \│"#,
-        r#"│ _\\_Λ  Λ   │"#,
-        r#"│   ฿(⚙  ⚙)\~│"#,
-        r#"│     )  (  \│"#,
-        r#"│     (..) 11│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 10, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│10   ┌───@┐ │"#,
-        r#"│     │____│ │"#,
-        r#"│  |\ (° ͜ʖ°) │"#,
-        r#"│   \\/    \ │"#,
-        r#"│   _\\_   / │"#,
-        r#"│     ฿\  /฿ │"#,
-        r#"│       || 10│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 9, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│9           │"#,
-        r#"│            │"#,
-        r#"│   |\       │"#,
-        r#"│    \\      │"#,
-        r#"│    _\\_    │"#,
-        r#"│      \     │"#,
-        r#"│           9│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 8, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│8           │"#,
-        r#"│            │"#,
-        r#"│   |\       │"#,
-        r#"│    \\      │"#,
-        r#"│    _\\_    │"#,
-        r#"│      \     │"#,
-        r#"│           8│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 7, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│7           │"#,
-        r#"│            │"#,
-        r#"│   |\       │"#,
-        r#"│    \\      │"#,
-        r#"│    _\\_    │"#,
-        r#"│      \     │"#,
-        r#"│           7│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 6, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│6           │"#,
-        r#"│            │"#,
-        r#"│   |\       │"#,
-        r#"│    \\      │"#,
-        r#"│    _\\_    │"#,
-        r#"│      \     │"#,
-        r#"│           6│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 5, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│5           │"#,
-        r#"│            │"#,
-        r#"│   |\       │"#,
-        r#"│    \\      │"#,
-        r#"│    _\\_    │"#,
-        r#"│      \     │"#,
-        r#"│           5│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 4, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│4           │"#,
-        r#"│            │"#,
-        r#"│   |\       │"#,
-        r#"│    \\      │"#,
-        r#"│    _\\_    │"#,
-        r#"│      \     │"#,
-        r#"│           4│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 3, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│3           │"#,
-        r#"│            │"#,
-        r#"│   |\       │"#,
-        r#"│    \\      │"#,
-        r#"│    _\\_    │"#,
-        r#"│      \     │"#,
-        r#"│           3│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 2, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│2           │"#,
-        r#"│            │"#,
-        r#"│   |\       │"#,
-        r#"│    \\      │"#,
-        r#"│    _\\_    │"#,
-        r#"│      \     │"#,
-        r#"│           2│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Sword, 1, make_str_card!(
-        r#"┌──  ────  ──┐"#,
-        r#"│1           │"#,
-        r#"│            │"#,
-        r#"│   |\       │"#,
-        r#"│    \\      │"#,
-        r#"│    _\\_    │"#,
-        r#"│      \     │"#,
-        r#"│           1│"#,
-        r#"└──  ────  ──┘"#)
-    );
-    deck.add(CardType::Club, 12, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│12  /^^^┼^\ │"#,
-        r#"│.-.(  ° ͜ʖ° )│"#,
-        r#"│(  )\     / │"#,
-        r#"│ ( )/---⚙-\ │"#,
-        r#"│  ()   .๏. \│"#,
-        r#"│  /    .๏.  │"#,
-        r#"│ /     .๏.12│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 11, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│11    ┌──@─┐│"#,
-        r#"│.-.   (° ͜ʖ°)│"#,
-        r#"│(  )  /    \│"#,
-        r#"│ ( ) Λ  Λ   │"#,
-        r#"│  ()(⚙  ⚙)\~│"#,
-        r#"│     )  (  \│"#,
-        r#"│     (..) 11│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 10, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│10   ┌───@┐ │"#,
-        r#"│.-.  │____│ │"#,
-        r#"│(  ) (° ͜ʖ°) │"#,
-        r#"│ ( ) /    \ │"#,
-        r#"│  ฿)/\    / │"#,
-        r#"│      \  /฿ │"#,
-        r#"│       || 10│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 9, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│9      .-.  │"#,
-        r#"│       (  ) │"#,
-        r#"│        ( ) │"#,
-        r#"│         () │"#,
-        r#"│            │"#,
-        r#"│            │"#,
-        r#"│           9│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 8, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│8           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│    (  )    │"#,
-        r#"│     ( )    │"#,
-        r#"│      ()    │"#,
-        r#"│           8│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 7, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│7           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│    (  )    │"#,
-        r#"│     ( )    │"#,
-        r#"│      ()    │"#,
-        r#"│           7│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 6, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│6           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│    (  )    │"#,
-        r#"│     ( )    │"#,
-        r#"│      ()    │"#,
-        r#"│           6│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 5, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│5           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│    (  )    │"#,
-        r#"│     ( )    │"#,
-        r#"│      ()    │"#,
-        r#"│           5│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 4, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│4           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│    (  )    │"#,
-        r#"│     ( )    │"#,
-        r#"│      ()    │"#,
-        r#"│           4│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 3, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│3           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│    (  )    │"#,
-        r#"│     ( )    │"#,
-        r#"│      ()    │"#,
-        r#"│           3│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 2, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│2           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│    (  )    │"#,
-        r#"│     ( )    │"#,
-        r#"│      ()    │"#,
-        r#"│           2│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-    deck.add(CardType::Club, 1, make_str_card!(
-        r#"┌─  ──  ──  ─┐"#,
-        r#"│1           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│    (  )    │"#,
-        r#"│     ( )    │"#,
-        r#"│      ()    │"#,
-        r#"│           1│"#,
-        r#"└─  ──  ──  ─┘"#)
-    );
-
-    deck.add(CardType::Gold, 12, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│12  /^^^┼^\ │"#,
-        r#"│   (  ° ͜ʖ° )│"#,
-        r#"│ .-.\     / │"#,
-        r#"│( O )---⚙-\ │"#,
-        r#"│ `฿`   .๏. \│"#,
-        r#"│  /    .๏.  │"#,
-        r#"│ /     .๏.12│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 11, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│11    ┌──@─┐│"#,
-        r#"│ .-.  (° ͜ʖ°)│"#,
-        r#"│( O ) /    \│"#,
-        r#"│ `-฿ Λ  Λ   │"#,
-        r#"│    (⚙  ⚙)\~│"#,
-        r#"│     )  (  \│"#,
-        r#"│     (..) 11│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 10, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│10   ┌───@┐ │"#,
-        r#"│     │____│ │"#,
-        r#"│ .-. (° ͜ʖ°) │"#,
-        r#"│( O )/    \ │"#,
-        r#"│ `฿` \    / │"#,
-        r#"│      \  /฿ │"#,
-        r#"│       || 10│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 9, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│9           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│   ( O )    │"#,
-        r#"│    `-`     │"#,
-        r#"│            │"#,
-        r#"│           9│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 8, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│8           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│   ( O )    │"#,
-        r#"│    `-`     │"#,
-        r#"│            │"#,
-        r#"│           8│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 7, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│7           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│   ( O )    │"#,
-        r#"│    `-`     │"#,
-        r#"│            │"#,
-        r#"│           7│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 6, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│6           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│   ( O )    │"#,
-        r#"│    `-`     │"#,
-        r#"│            │"#,
-        r#"│           6│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 5, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│5           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│   ( O )    │"#,
-        r#"│    `-`     │"#,
-        r#"│            │"#,
-        r#"│           5│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 4, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│4           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│   ( O )    │"#,
-        r#"│    `-`     │"#,
-        r#"│            │"#,
-        r#"│           4│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 3, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│3           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│   ( O )    │"#,
-        r#"│    `-`     │"#,
-        r#"│            │"#,
-        r#"│           3│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 2, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│2           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│   ( O )    │"#,
-        r#"│    `-`     │"#,
-        r#"│            │"#,
-        r#"│           2│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Gold, 1, make_str_card!(
-        r#"┌────────────┐"#,
-        r#"│1           │"#,
-        r#"│            │"#,
-        r#"│    .-.     │"#,
-        r#"│   ( O )    │"#,
-        r#"│    `-`     │"#,
-        r#"│            │"#,
-        r#"│           1│"#,
-        r#"└────────────┘"#)
-    );
-    deck.add(CardType::Cup, 12, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│12  /^^^┼^\ │"#,
-        r#"│   (  ° ͜ʖ° )│"#,
-        r#"│ ___\     / │"#,
-        r#"│(___)---⚙-\ │"#,
-        r#"│ ฿_/   .๏. \│"#,
-        r#"│  /    .๏.  │"#,
-        r#"│ /     .๏.12│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 11, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│11    ┌──@─┐│"#,
-        r#"│ ___  (° ͜ʖ°)│"#,
-        r#"│(___) /    \│"#,
-        r#"│ \_฿ Λ  Λ   │"#,
-        r#"│    (⚙  ⚙)\~│"#,
-        r#"│     )  (  \│"#,
-        r#"│     (..) 11│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 10, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│10   ┌───@┐ │"#,
-        r#"│     │____│ │"#,
-        r#"│ ___ (° ͜ʖ°) │"#,
-        r#"│(___)/    \ │"#,
-        r#"│ \_฿ \    / │"#,
-        r#"│      \  /฿ │"#,
-        r#"│       || 10│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 9, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│9           │"#,
-        r#"│            │"#,
-        r#"│    ___     │"#,
-        r#"│   (___)    │"#,
-        r#"│    \_/     │"#,
-        r#"│            │"#,
-        r#"│           9│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 8, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│8           │"#,
-        r#"│            │"#,
-        r#"│    ___     │"#,
-        r#"│   (___)    │"#,
-        r#"│    \_/     │"#,
-        r#"│            │"#,
-        r#"│           8│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 7, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│7           │"#,
-        r#"│            │"#,
-        r#"│    ___     │"#,
-        r#"│   (___)    │"#,
-        r#"│    \_/     │"#,
-        r#"│            │"#,
-        r#"│           7│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 6, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│6           │"#,
-        r#"│            │"#,
-        r#"│    ___     │"#,
-        r#"│   (___)    │"#,
-        r#"│    \_/     │"#,
-        r#"│            │"#,
-        r#"│           6│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 5, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│5           │"#,
-        r#"│            │"#,
-        r#"│    ___     │"#,
-        r#"│   (___)    │"#,
-        r#"│    \_/     │"#,
-        r#"│            │"#,
-        r#"│           5│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 4, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│4           │"#,
-        r#"│            │"#,
-        r#"│    ___     │"#,
-        r#"│   (___)    │"#,
-        r#"│    \_/     │"#,
-        r#"│            │"#,
-        r#"│           4│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 3, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│3           │"#,
-        r#"│            │"#,
-        r#"│    ___     │"#,
-        r#"│   (___)    │"#,
-        r#"│    \_/     │"#,
-        r#"│            │"#,
-        r#"│           3│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 2, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│2           │"#,
-        r#"│            │"#,
-        r#"│    ___     │"#,
-        r#"│   (___)    │"#,
-        r#"│    \_/     │"#,
-        r#"│            │"#,
-        r#"│           2│"#,
-        r#"└────    ────┘"#)
-    );
-    deck.add(CardType::Cup, 1, make_str_card!(
-        r#"┌────    ────┐"#,
-        r#"│1           │"#,
-        r#"│            │"#,
-        r#"│    ___     │"#,
-        r#"│   (___)    │"#,
-        r#"│    \_/     │"#,
-        r#"│            │"#,
-        r#"│           1│"#,
-        r#"└────    ────┘"#)
-    );
+#[test]
+fn plain_card_test() {
+    // a custom deck may have cards no theme draws, a cup 13 falls back to a plain card
+    let card = Card::new(CardType::Cup, 13);
+    assert!(!Theme::embedded().fronts.contains_key(&(card._type, card.value)));
+    let visual = plain_card(&card);
+    assert_eq!(visual.len(), CARD_HEIGHT);
+    assert!(visual.iter().all(|row| row.chars().count() == CARD_WIDTH));
+    assert_eq!(visual[1], "│13          │");
+    assert_eq!(visual[4], "│     U      │");
+    assert_eq!(plain_card(&Card::with_worth(CardType::Heart, 11, 10))[5], "│    =10     │");
 }
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::common::CardType;
+use crate::decks;
+
+pub const CARD_WIDTH: usize = 14;
+pub const CARD_HEIGHT: usize = 9;
+
+const DEFAULT_THEME: &str = include_str!("../themes/default.theme");
+const FRENCH_THEME: &str = include_str!("../themes/french.theme");
+
+// The faces of the cards and their back, as rows of text
+pub struct Theme {
+    pub back: Vec<String>,
+    pub fronts: HashMap<(CardType, u8), Vec<String>>,
+}
+
+// Combining marks are drawn over the previous character, they take no column
+fn width(row: &str) -> usize {
+    row.chars().filter(|c| !matches!(*c as u32,
+        0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x20D0..=0x20FF | 0xFE20..=0xFE2F)).count()
+}
+
+enum Face {
+    Back,
+    Front(CardType, u8),
+}
+
+fn header(line: &str) -> Option<Face> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let mut words = inner.split_whitespace();
+    match (words.next()?, words.next(), words.next()) {
+        ("back", None, _) => Some(Face::Back),
+        (suit, Some(value), None) => Some(Face::Front(decks::suit(suit)?, value.parse().ok()?)),
+        _ => None,
+    }
+}
+
+impl Theme {
+    // The theme built in the program, always complete for the Spanish and
+    // French decks. The cards of a custom deck it does not have are drawn
+    // plain by the terminal
+    pub fn embedded() -> Self {
+        let mut theme = Theme { back: Vec::new(), fronts: HashMap::new() };
+        theme.parse(DEFAULT_THEME).expect("the embedded theme is valid");
+        theme.parse(FRENCH_THEME).expect("the embedded French theme is valid");
+        theme
+    }
+
+    // A theme file, or a directory of them, over the embedded theme:
+    // the cards it does not draw keep the default faces
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut theme = Theme::embedded();
+        let mut files = Vec::new();
+        if Path::new(path).is_dir() {
+            let entries = fs::read_dir(path).map_err(|e| format!("{}: {}", path, e))?;
+            for entry in entries {
+                files.push(entry.map_err(|e| format!("{}: {}", path, e))?.path());
+            }
+            files.sort();
+        } else {
+            files.push(Path::new(path).to_path_buf());
+        }
+        for file in files {
+            let text = fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+            theme.parse(&text).map_err(|e| format!("{}: {}", file.display(), e))?;
+        }
+        Ok(theme)
+    }
+
+    // Every face is a `[<suit> <value>]` or `[back]` line and the rows under it.
+    // Comments and blank lines go between faces, the rows are taken as they are
+    fn parse(&mut self, text: &str) -> Result<(), String> {
+        let mut lines = text.lines().enumerate();
+        while let Some((start, line)) = lines.next() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let face = header(line.trim()).ok_or(format!("line {}: expected a card header", start + 1))?;
+            let mut rows = Vec::new();
+            for _ in 0..CARD_HEIGHT {
+                match lines.next() {
+                    Some((_, row)) if width(row) == CARD_WIDTH => rows.push(row.to_string()),
+                    Some((number, _)) =>
+                        return Err(format!("line {}: a row has to be {} columns wide", number + 1, CARD_WIDTH)),
+                    None => return Err(format!("line {}: the card has less than {} rows", start + 1, CARD_HEIGHT)),
+                }
+            }
+            match face {
+                Face::Back => self.back = rows,
+                Face::Front(suit, value) => { self.fronts.insert((suit, value), rows); }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn embedded_test() {
+    let theme = Theme::embedded();
+    assert_eq!(theme.back.len(), CARD_HEIGHT);
+    for kind in [crate::common::DeckKind::Spanish, crate::common::DeckKind::French] {
+        for card in decks::cards(&kind) {
+            assert!(theme.fronts.contains_key(&(card._type, card.value)), "{:?}", card);
+        }
+    }
+    assert_ne!(theme.fronts[&(CardType::Heart, 13)], theme.fronts[&(CardType::Spade, 13)]);
+    assert_ne!(theme.fronts[&(CardType::Joker, 1)], theme.fronts[&(CardType::Cup, 1)]);
+}
+
+#[test]
+fn parse_test() {
+    let mut theme = Theme::embedded();
+    let face: Vec<String> = (0..CARD_HEIGHT).map(|_| "│".repeat(CARD_WIDTH)).collect();
+    assert_eq!(theme.parse(&format!("# mine\n[heart 1]\n{}\n", face.join("\n"))), Ok(()));
+    assert_eq!(theme.fronts[&(CardType::Heart, 1)], face);
+    assert_eq!(theme.parse("[heart 2]\n│││\n"), Err("line 2: a row has to be 14 columns wide".to_string()));
+    assert_eq!(theme.parse("[star 2]\n"), Err("line 1: expected a card header".to_string()));
+    assert_eq!(theme.parse(&format!("[back]\n{}", face[..3].join("\n"))), Err("line 1: the card has less than 9 rows".to_string()));
+
+    // a blank row or one starting with # belongs to the face
+    let mut face = face;
+    face[3] = " ".repeat(CARD_WIDTH);
+    face[4] = "#".repeat(CARD_WIDTH);
+    assert_eq!(theme.parse(&format!("\n# spaced\n[heart 3]\n{}\n\n", face.join("\n"))), Ok(()));
+    assert_eq!(theme.fronts[&(CardType::Heart, 3)], face);
+}